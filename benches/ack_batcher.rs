@@ -0,0 +1,63 @@
+//! `cargo bench --bench ack_batcher`
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use dre::{ack_batcher::AckBatcher, ConnectionState, PacketState, RateSample};
+
+/// One second at 100k `ACK`s per second
+const ACKS: u32 = 100_000;
+const MIN_RTT: Duration = Duration::from_millis(20);
+
+/// A windowed max filter over the samples, standing in for a controller
+#[derive(Default)]
+struct Filter {
+    max: f64,
+}
+impl Filter {
+    fn update(&mut self, rs: Option<RateSample>) {
+        if let Some(rs) = rs {
+            self.max = self.max.max(rs.delivery_rate_raw());
+        }
+    }
+}
+
+fn bench(
+    name: &str,
+    mut on_ack: impl FnMut(&mut ConnectionState, (PacketState, u64), Instant) -> Option<RateSample>,
+) {
+    let start = Instant::now();
+    let step = Duration::from_secs(1) / ACKS;
+    let mut c = ConnectionState::new(start);
+    let mut filter = Filter::default();
+    let in_flight = (MIN_RTT.as_nanos() / step.as_nanos()) as u32;
+    let mut sent = std::collections::VecDeque::new();
+    let mut elapsed = Duration::ZERO;
+    for k in 0..ACKS + in_flight {
+        let now = start + k * step;
+        if in_flight <= k {
+            let packet = sent.pop_front().unwrap();
+            let timer = Instant::now();
+            filter.update(on_ack(&mut c, packet, now));
+            elapsed += timer.elapsed();
+        }
+        if k < ACKS {
+            sent.push_back((c.send_packet_2(now, k == 0), 1_200));
+        }
+    }
+    black_box(filter.max);
+    println!("{name}: {:?} per ACK", elapsed / ACKS);
+}
+
+fn main() {
+    bench("per ACK", |c, packet, now| {
+        c.sample_rate_iter([packet], now, MIN_RTT)
+    });
+
+    let mut batcher = AckBatcher::new(1.);
+    bench("batched", |c, packet, now| {
+        batcher.on_ack(c, [packet], now, MIN_RTT)
+    });
+}
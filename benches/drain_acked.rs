@@ -0,0 +1,60 @@
+//! `cargo bench --bench drain_acked`
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use dre::{
+    packet_store::{drain_acked, PacketMap},
+    ConnectionState, Packet,
+};
+
+const PACKETS: u64 = 1_000;
+const ROUNDS: u32 = 1_000;
+
+fn fill(c: &mut ConnectionState, now: Instant) -> PacketMap {
+    (0..PACKETS)
+        .map(|pkt_num| (pkt_num, (c.send_packet_2(now, pkt_num == 0), 1_200)))
+        .collect()
+}
+
+fn bench(name: &str, mut f: impl FnMut(&mut ConnectionState, &mut PacketMap, Instant)) {
+    let now = Instant::now();
+    let mut c = ConnectionState::new(now);
+    let mut elapsed = Duration::ZERO;
+    for _ in 0..ROUNDS {
+        let mut store = fill(&mut c, now);
+        let start = Instant::now();
+        f(&mut c, &mut store, now);
+        elapsed += start.elapsed();
+        black_box(store);
+    }
+    println!("{name}: {:?} per ACK", elapsed / ROUNDS);
+}
+
+fn main() {
+    let ranges = [0..=PACKETS / 2, PACKETS / 2 + 2..=PACKETS];
+
+    bench("naive", |c, store, now| {
+        let mut acked = vec![];
+        for range in &ranges {
+            for (_, (state, data_length)) in store.range(range.clone()) {
+                acked.push(Packet {
+                    state: *state,
+                    data_length: *data_length,
+                });
+            }
+        }
+        black_box(c.sample_rate(&acked, now, Duration::ZERO));
+        for range in &ranges {
+            for pkt_num in range.clone() {
+                store.remove(&pkt_num);
+            }
+        }
+    });
+
+    bench("drain_acked", |c, store, now| {
+        black_box(c.sample_rate_iter(drain_acked(store, &ranges), now, Duration::ZERO));
+    });
+}
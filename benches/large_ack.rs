@@ -0,0 +1,45 @@
+//! `cargo bench --bench large_ack`
+
+use std::{
+    hint::black_box,
+    num::NonZeroUsize,
+    time::{Duration, Instant},
+};
+
+use dre::{ConnectionState, DeliveryRateConfig, Packet};
+
+const PACKETS: u64 = 50_000;
+const ROUNDS: u32 = 100;
+
+fn bench(name: &str, config: DeliveryRateConfig) {
+    let now = Instant::now();
+    let mut c = ConnectionState::with_config(config, now);
+    let mut elapsed = Duration::ZERO;
+    for round in 0..ROUNDS {
+        let acked: Vec<Packet> = (0..PACKETS)
+            .map(|i| {
+                let state = c.send_packet_2(now, round == 0 && i == 0);
+                Packet {
+                    state,
+                    data_length: 1_200,
+                }
+                .with_id(u64::from(round) * PACKETS + i)
+            })
+            .collect();
+        let start = Instant::now();
+        black_box(c.sample_rate(&acked, now, Duration::ZERO));
+        elapsed += start.elapsed();
+    }
+    println!("{name}: {:?} per ACK", elapsed / ROUNDS);
+}
+
+fn main() {
+    bench("serial", DeliveryRateConfig::default());
+    bench(
+        "parallel",
+        DeliveryRateConfig {
+            parallel_threshold: NonZeroUsize::new(10_000),
+            ..Default::default()
+        },
+    );
+}
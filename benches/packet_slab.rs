@@ -0,0 +1,66 @@
+//! `cargo bench --bench packet_slab`
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use dre::{
+    packet_store::{PacketMap, PacketSlab, SlabGrowth},
+    ConnectionState,
+};
+
+/// Packets in flight
+const IN_FLIGHT: u64 = 200_000;
+const ACKS: u64 = 1_000_000;
+
+/// Keep `IN_FLIGHT` packets outstanding, each `ACK` acknowledging the oldest one and sending a new one
+fn bench<S>(
+    name: &str,
+    store: &mut S,
+    send: impl Fn(&mut S, &mut ConnectionState, u64, Instant),
+    ack: impl Fn(&mut S, &mut ConnectionState, u64, Instant),
+) {
+    let now = Instant::now();
+    let mut c = ConnectionState::new(now);
+    for pkt_num in 0..IN_FLIGHT {
+        send(store, &mut c, pkt_num, now);
+    }
+    let start = Instant::now();
+    for pkt_num in 0..ACKS {
+        ack(store, &mut c, pkt_num, now);
+        send(store, &mut c, IN_FLIGHT + pkt_num, now);
+    }
+    let elapsed = start.elapsed();
+    black_box(c.round_count());
+    println!("{name}: {:?} per ACK", elapsed / ACKS as u32);
+}
+
+fn main() {
+    bench(
+        "BTreeMap",
+        &mut PacketMap::new(),
+        |map, c, pkt_num, now| {
+            map.insert(pkt_num, (c.send_packet_2(now, pkt_num == 0), 1_200));
+        },
+        |map, c, pkt_num, now| {
+            black_box(c.sample_rate_iter(map.remove(&pkt_num), now, Duration::ZERO));
+        },
+    );
+
+    // The handles live in the caller's own per-packet metadata, here a ring indexed by packet number
+    let slab = PacketSlab::with_capacity(IN_FLIGHT as usize, SlabGrowth::Double);
+    let handles = vec![None; IN_FLIGHT as usize];
+    bench(
+        "PacketSlab",
+        &mut (slab, handles),
+        |(slab, handles), c, pkt_num, now| {
+            let handle = slab.send_packet(c, now, pkt_num == 0, 1_200);
+            handles[(pkt_num % IN_FLIGHT) as usize] = Some(handle);
+        },
+        |(slab, handles), c, pkt_num, now| {
+            let handle = handles[(pkt_num % IN_FLIGHT) as usize].take().unwrap();
+            black_box(c.sample_rate_iter(slab.take(handle), now, Duration::ZERO));
+        },
+    );
+}
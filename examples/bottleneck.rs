@@ -0,0 +1,338 @@
+//! A complete sender loop over a simulated bottleneck link
+//!
+//! Application writes, app-limited detection, paced sends, `ACK` processing, rate sampling, and a windowed max filter, all wired together.
+//!
+//! ```text
+//! cargo run --example bottleneck -- --rate-mbps 20 --rtt-ms 40 --queue 100 --pattern bulk
+//! cargo run --example bottleneck -- --pattern periodic
+//! cargo run --example bottleneck -- --estimator westwood
+//! ```
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use dre::{
+    cold_start::ColdStartBandwidth,
+    estimator::{BandwidthEstimator, WestwoodBandwidth, WindowedMaxBandwidth},
+    ConnectionSenderState, ConnectionState, PacketState, TransportSendSequenceSpace,
+};
+
+const MSS: u64 = 1_200;
+const INITIAL_CWND: u64 = 10 * MSS;
+const PACING_GAIN: f64 = 1.25;
+const CWND_GAIN: u64 = 2;
+/// The max filter covers this many round trips
+const BW_FILTER_ROUNDS: u64 = 10;
+const STEP: Duration = Duration::from_micros(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pattern {
+    /// The application always has data to send
+    Bulk,
+    /// The application writes 100 KB every 200 ms
+    Periodic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Estimator {
+    /// [`WindowedMaxBandwidth`]
+    WindowedMax,
+    /// [`WestwoodBandwidth`]
+    Westwood,
+}
+impl Estimator {
+    fn build(self) -> Box<dyn BandwidthEstimator> {
+        match self {
+            Self::WindowedMax => Box::new(WindowedMaxBandwidth::new(BW_FILTER_ROUNDS)),
+            Self::Westwood => Box::new(WestwoodBandwidth::default()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Config {
+    /// Measured in bits per second
+    link_rate: f64,
+    rtt: Duration,
+    /// Measured in packets
+    queue_depth: usize,
+    pattern: Pattern,
+    estimator: Estimator,
+    duration: Duration,
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            link_rate: 20e6,
+            rtt: Duration::from_millis(40),
+            queue_depth: 100,
+            pattern: Pattern::Bulk,
+            estimator: Estimator::WindowedMax,
+            duration: Duration::from_secs(5),
+        }
+    }
+}
+impl Config {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut config = Self::default();
+        while let Some(flag) = args.next() {
+            let value = args.next().ok_or(format!("missing value for `{flag}`"))?;
+            let number = || value.parse::<f64>().map_err(|e| format!("`{flag}`: {e}"));
+            match flag.as_str() {
+                "--rate-mbps" => config.link_rate = number()? * 1e6,
+                "--rtt-ms" => config.rtt = Duration::from_secs_f64(number()? / 1e3),
+                "--queue" => config.queue_depth = number()? as usize,
+                "--duration-s" => config.duration = Duration::from_secs_f64(number()?),
+                "--pattern" => {
+                    config.pattern = match value.as_str() {
+                        "bulk" => Pattern::Bulk,
+                        "periodic" => Pattern::Periodic,
+                        _ => return Err(format!("unknown pattern `{value}`")),
+                    }
+                }
+                "--estimator" => {
+                    config.estimator = match value.as_str() {
+                        "max" => Estimator::WindowedMax,
+                        "westwood" => Estimator::Westwood,
+                        _ => return Err(format!("unknown estimator `{value}`")),
+                    }
+                }
+                _ => return Err(format!("unknown flag `{flag}`")),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// What the sender observed at a point in time
+#[derive(Debug, Clone, Copy)]
+struct Tick {
+    elapsed: Duration,
+    /// Measured in bits per second
+    bandwidth: f64,
+    is_app_limited: bool,
+}
+
+/// Drop-tail bottleneck followed by propagation delay
+#[derive(Debug)]
+struct Link {
+    /// Measured in octets per second
+    rate: f64,
+    rtt: Duration,
+    queue_depth: usize,
+    /// Departure times of the packets still queued
+    queue: VecDeque<Instant>,
+    free_at: Instant,
+    /// (ACK arrival time, packet number), in arrival order
+    acks: VecDeque<(Instant, u64)>,
+}
+impl Link {
+    /// Return whether the packet fits in the queue
+    fn send(&mut self, now: Instant, pkt_num: u64, len: u64) -> bool {
+        while self
+            .queue
+            .front()
+            .is_some_and(|departure| *departure <= now)
+        {
+            self.queue.pop_front();
+        }
+        if self.queue_depth <= self.queue.len() {
+            return false;
+        }
+        let departure = self.free_at.max(now) + Duration::from_secs_f64(len as f64 / self.rate);
+        self.free_at = departure;
+        self.queue.push_back(departure);
+        self.acks.push_back((departure + self.rtt, pkt_num));
+        true
+    }
+
+    fn poll_ack(&mut self, now: Instant) -> Option<u64> {
+        let (arrival, _) = self.acks.front()?;
+        if now < *arrival {
+            return None;
+        }
+        self.acks.pop_front().map(|(_, pkt_num)| pkt_num)
+    }
+}
+
+fn run(config: &Config, mut on_tick: impl FnMut(Tick)) -> Option<f64> {
+    let start = Instant::now();
+    let mut now = start;
+    let mut c = ConnectionState::new(now);
+    let mut snd = TransportSendSequenceSpace {
+        nxt: 0,
+        una: 0,
+        mss: MSS,
+        wnd: INITIAL_CWND,
+        rwnd: u64::MAX,
+    };
+    let mut sender = ConnectionSenderState {
+        write_seq: 0,
+        pending_transmissions: 0,
+        pending_probe_transmissions: 0,
+        lost_out: 0,
+        retrans_out: 0,
+        pipe: 0,
+    };
+    let mut link = Link {
+        rate: config.link_rate / 8.,
+        rtt: config.rtt,
+        queue_depth: config.queue_depth,
+        queue: VecDeque::new(),
+        free_at: now,
+        acks: VecDeque::new(),
+    };
+    let mut in_flight: BTreeMap<u64, (PacketState, u64)> = BTreeMap::new();
+    let mut lost: VecDeque<(Instant, u64)> = VecDeque::new();
+    let mut next_pkt_num = 0;
+    let mut next_send = now;
+    let mut next_write = now;
+    let mut next_tick = now;
+    let mut cold_start = ColdStartBandwidth::new(INITIAL_CWND);
+    let mut estimator = config.estimator.build();
+    let mut is_app_limited = false;
+
+    while now - start < config.duration {
+        // Application writes
+        if next_write <= now {
+            c.detect_application_limited_phases(&sender, &snd);
+            match config.pattern {
+                Pattern::Bulk => {
+                    sender.write_seq = snd.nxt + 64 * MSS;
+                    next_write = now;
+                }
+                Pattern::Periodic => {
+                    sender.write_seq += 100_000;
+                    next_write = now + Duration::from_millis(200);
+                }
+            }
+        }
+
+        // ACK processing
+        let mut acked = vec![];
+        while let Some(pkt_num) = link.poll_ack(now) {
+            acked.extend(in_flight.remove(&pkt_num));
+        }
+        while lost.front().is_some_and(|(detected, _)| *detected <= now) {
+            let (_, pkt_num) = lost.pop_front().unwrap();
+            if let Some((_, len)) = in_flight.remove(&pkt_num) {
+                sender.pipe -= len;
+            }
+        }
+        if !acked.is_empty() {
+            c.detect_application_limited_phases(&sender, &snd);
+            sender.pipe -= acked.iter().map(|(_, len)| len).sum::<u64>();
+            if let Some(rs) = c.sample_rate_iter(acked, now, Duration::ZERO) {
+                cold_start.on_sample(&rs);
+                is_app_limited = rs.is_app_limited();
+                estimator.on_sample(&rs);
+            }
+        }
+        snd.una = snd.nxt - sender.pipe;
+
+        // Congestion control
+        let bandwidth = estimator
+            .bandwidth()
+            .or(cold_start.bandwidth(Some(config.rtt)))
+            .unwrap()
+            .as_bytes_per_sec();
+        let bdp = (bandwidth * config.rtt.as_secs_f64()) as u64;
+        snd.wnd = (CWND_GAIN * bdp).max(4 * MSS);
+
+        // Paced sends
+        while next_send <= now && sender.pipe + MSS <= snd.wnd && snd.nxt + MSS <= sender.write_seq
+        {
+            let state = c.send_packet(now, &snd);
+            let pkt_num = next_pkt_num;
+            next_pkt_num += 1;
+            if !link.send(now, pkt_num, MSS) {
+                // Detected as lost an RTT later
+                lost.push_back((now + config.rtt, pkt_num));
+            }
+            in_flight.insert(pkt_num, (state, MSS));
+            snd.nxt += MSS;
+            sender.pipe += MSS;
+            next_send += Duration::from_secs_f64(MSS as f64 / (PACING_GAIN * bandwidth));
+        }
+        next_send = next_send.max(now);
+
+        if next_tick <= now {
+            on_tick(Tick {
+                elapsed: now - start,
+                bandwidth: bandwidth * 8.,
+                is_app_limited,
+            });
+            next_tick += Duration::from_millis(200);
+        }
+        now += STEP;
+    }
+    estimator.bandwidth().map(|bw| bw.as_bytes_per_sec() * 8.)
+}
+
+fn main() {
+    let config = match Config::from_args(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e}");
+            eprintln!(
+                "usage: bottleneck [--rate-mbps N] [--rtt-ms N] [--queue PACKETS] [--pattern bulk|periodic] [--estimator max|westwood] [--duration-s N]"
+            );
+            std::process::exit(2);
+        }
+    };
+    println!("{config:?}");
+    let bandwidth = run(&config, |tick| {
+        println!(
+            "{:>6.1} s  {:>7.2} Mbps  {}",
+            tick.elapsed.as_secs_f64(),
+            tick.bandwidth / 1e6,
+            if tick.is_app_limited {
+                "app-limited"
+            } else {
+                "network-limited"
+            }
+        );
+    });
+    if let Some(bandwidth) = bandwidth {
+        println!("final estimate: {:.2} Mbps", bandwidth / 1e6);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bulk_converges() {
+        let config = Config::default();
+        let bandwidth = run(&config, |_| {}).unwrap();
+        let error = (bandwidth - config.link_rate).abs() / config.link_rate;
+        assert!(error < 0.1, "{bandwidth}");
+    }
+
+    #[test]
+    fn test_bulk_converges_westwood() {
+        let config = Config {
+            estimator: Estimator::Westwood,
+            ..Default::default()
+        };
+        let bandwidth = run(&config, |_| {}).unwrap();
+        let error = (bandwidth - config.link_rate).abs() / config.link_rate;
+        assert!(error < 0.1, "{bandwidth}");
+    }
+
+    #[test]
+    fn test_periodic_is_app_limited() {
+        let config = Config {
+            pattern: Pattern::Periodic,
+            ..Default::default()
+        };
+        let mut app_limited_ticks = 0;
+        run(&config, |tick| {
+            app_limited_ticks += tick.is_app_limited as usize
+        });
+        assert!(0 < app_limited_ticks);
+    }
+}
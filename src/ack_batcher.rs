@@ -0,0 +1,163 @@
+use std::time::{Duration, Instant};
+
+use crate::{AckedPacket, ConnectionState, PacketState, RateSample};
+
+/// Accumulates acknowledged packets across `ACK`s and samples at most once per `min_rtt` (or a fraction of it)
+///
+/// A sample per `ACK` carries little more information than a sample per round trip, so at high `ACK` rates batching saves the sampling and filtering work.
+/// - No delivered data is lost: accumulated packets are carried forward until the next emission.
+/// - [`ConnectionState`] only learns of the accumulated packets on emission, so its delivery information (and the snapshots of packets sent meanwhile) lags by up to one batch.
+#[derive(Debug, Clone)]
+pub struct AckBatcher {
+    /// Emit once this fraction of `min_rtt` has elapsed since the last emission
+    rtt_fraction: f64,
+    pending: Vec<(PacketState, u64)>,
+    last_emission: Option<Instant>,
+}
+impl AckBatcher {
+    /// `rtt_fraction`: e.g. `1.0` for at most one sample per `min_rtt`
+    pub fn new(rtt_fraction: f64) -> Self {
+        Self {
+            rtt_fraction,
+            pending: vec![],
+            last_emission: None,
+        }
+    }
+
+    /// Upon receiving `ACK`, like [`ConnectionState::sample_rate_iter()`]
+    ///
+    /// The first `ACK` always emits.
+    pub fn on_ack<I>(
+        &mut self,
+        c: &mut ConnectionState,
+        acked_packets: I,
+        now: Instant,
+        min_rtt: Duration,
+    ) -> Option<RateSample>
+    where
+        I: IntoIterator,
+        I::Item: AckedPacket,
+    {
+        self.pending.extend(
+            acked_packets
+                .into_iter()
+                .map(|p| (*p.state(), p.data_length())),
+        );
+        let batch_time = min_rtt.mul_f64(self.rtt_fraction);
+        if self
+            .last_emission
+            .is_some_and(|last| now.saturating_duration_since(last) < batch_time)
+        {
+            return None;
+        }
+        self.flush(c, now, min_rtt)
+    }
+
+    /// Upon data being marked as lost
+    ///
+    /// Reports the loss to [`ConnectionState::on_loss()`] and flushes, so the controller reacts on up-to-date delivery information.
+    pub fn on_loss(
+        &mut self,
+        c: &mut ConnectionState,
+        lost: u64,
+        now: Instant,
+        min_rtt: Duration,
+    ) -> Option<RateSample> {
+        c.on_loss(lost);
+        self.flush(c, now, min_rtt)
+    }
+
+    /// Credit all the accumulated packets now
+    pub fn flush(
+        &mut self,
+        c: &mut ConnectionState,
+        now: Instant,
+        min_rtt: Duration,
+    ) -> Option<RateSample> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        self.last_emission = Some(now);
+        c.sample_rate_iter(self.pending.drain(..), now, min_rtt)
+    }
+
+    /// The number of accumulated packets not yet credited
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One packet per millisecond over a path of `rtt`, returning the max sampled rate and the number of samples
+    fn run(
+        c: &mut ConnectionState,
+        mut on_ack: impl FnMut(&mut ConnectionState, (PacketState, u64), Instant) -> Option<RateSample>,
+    ) -> (f64, usize) {
+        let start = Instant::now();
+        let ms = Duration::from_millis(1);
+        let rtt = 100;
+        let packets = 1_000;
+        let mut sent: Vec<(PacketState, u64)> = vec![];
+        let mut max = 0.;
+        let mut samples = 0;
+        for k in 0..packets + rtt {
+            let now = start + k as u32 * ms;
+            if rtt <= k {
+                if let Some(rs) = on_ack(c, sent[k - rtt], now) {
+                    max = rs.delivery_rate_raw().max(max);
+                    samples += 1;
+                }
+            }
+            if k < packets {
+                sent.push((c.send_packet_2(now, k == 0), 1));
+            }
+        }
+        (max, samples)
+    }
+
+    #[test]
+    fn test_against_per_ack() {
+        let min_rtt = Duration::from_millis(100);
+        let now = Instant::now();
+
+        let mut per_ack = ConnectionState::new(now);
+        let (per_ack_max, per_ack_samples) = run(&mut per_ack, |c, p, now| {
+            c.sample_rate_iter([p], now, min_rtt)
+        });
+
+        let mut batched = ConnectionState::new(now);
+        let mut batcher = AckBatcher::new(1.);
+        let (batched_max, batched_samples) = run(&mut batched, |c, p, now| {
+            batcher.on_ack(c, [p], now, min_rtt)
+        });
+        batcher.flush(&mut batched, now, min_rtt);
+        assert_eq!(batcher.pending(), 0);
+
+        assert_eq!(per_ack.stats().delivered, 1_000);
+        assert_eq!(batched.stats().delivered, 1_000);
+        assert!(batched_samples * 10 < per_ack_samples);
+        let error = (batched_max - per_ack_max).abs() / per_ack_max;
+        assert!(error < 0.05, "{batched_max} {per_ack_max}");
+    }
+
+    #[test]
+    fn test_flush_on_loss() {
+        let now = Instant::now();
+        let min_rtt = Duration::from_secs(1);
+        let ms = Duration::from_millis(1);
+        let mut c = ConnectionState::new(now);
+        let mut batcher = AckBatcher::new(1.);
+        let p: Vec<_> = (0..3).map(|i| (c.send_packet_2(now, i == 0), 1)).collect();
+        batcher.on_ack(&mut c, [p[0]], now + 100 * ms, min_rtt);
+        assert_eq!(batcher.pending(), 0);
+        batcher.on_ack(&mut c, [p[1]], now + 200 * ms, min_rtt);
+        assert_eq!(batcher.pending(), 1);
+        batcher.on_loss(&mut c, 1, now + 300 * ms, min_rtt);
+        assert_eq!(batcher.pending(), 0);
+        assert_eq!(c.stats().delivered, 2);
+        assert_eq!(c.stats().lost, 1);
+    }
+}
@@ -0,0 +1,244 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use crate::RateSample;
+
+/// Histogram bins per doubling of the rate; a bin is about 4.4% wide
+const BINS_PER_OCTAVE: f64 = 16.;
+
+/// Summary statistics of the samples in a [`RateSampleAggregator`] window
+///
+/// The rates are measured in octets or packets per second; [`None`] if the window holds no sample.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Summary {
+    pub samples: u64,
+    pub mean_rate: Option<f64>,
+    /// Approximated to within half a histogram bin (about 2.2%)
+    pub p95_rate: Option<f64>,
+    pub max_rate: Option<f64>,
+    pub app_limited_fraction: Option<f64>,
+    /// The sum of [`RateSample::delivered()`], measured in octets or packets
+    pub delivered: u64,
+}
+
+/// Summary statistics over a wall-clock window of rate samples, e.g. for dashboards
+///
+/// Memory is bounded by the number of buckets: samples are folded into sub-windows of `window / buckets` each, and whole sub-windows expire at once.
+/// - The window covers the bucket `now` falls into plus the `buckets - 1` preceding ones, so it spans between `window - window / buckets` and `window`.
+/// - Queried exactly on a bucket boundary, the bucket starting at `now` is empty and the bucket that just ended still counts.
+/// - Buckets are aligned to the first push.
+#[derive(Debug, Clone)]
+pub struct RateSampleAggregator {
+    bucket_width: Duration,
+    buckets: usize,
+    origin: Option<Instant>,
+    /// Ordered by [`Bucket::index`]
+    active: VecDeque<Bucket>,
+}
+impl RateSampleAggregator {
+    /// `buckets`: the number of sub-windows `window` is split into
+    pub fn new(window: Duration, buckets: usize) -> Self {
+        let buckets = buckets.max(1);
+        Self {
+            bucket_width: window / buckets as u32,
+            buckets,
+            origin: None,
+            active: VecDeque::new(),
+        }
+    }
+
+    /// Unreliable samples are ignored
+    pub fn push(&mut self, sample: &RateSample, now: Instant) {
+        if !sample.is_reliable() {
+            return;
+        }
+        let origin = *self.origin.get_or_insert(now);
+        let index = self.bucket_index(origin, now);
+        self.expire(index);
+        if self.active.back().is_none_or(|b| b.index < index) {
+            self.active.push_back(Bucket::new(index));
+        }
+        // `now` going backwards lands in the newest bucket
+        let bucket = self.active.back_mut().unwrap();
+        let rate = sample.delivery_rate_raw();
+        bucket.samples += 1;
+        bucket.rate_sum += rate;
+        bucket.max_rate = bucket.max_rate.max(rate);
+        bucket.app_limited += sample.is_app_limited() as u64;
+        bucket.delivered += sample.delivered();
+        *bucket.histogram.entry(bin(rate)).or_default() += 1;
+    }
+
+    pub fn summary(&self, now: Instant) -> Summary {
+        let Some(origin) = self.origin else {
+            return Summary::default();
+        };
+        let index = self.bucket_index(origin, now);
+        let oldest = (index + 1).saturating_sub(self.buckets as u64);
+        let in_window = || {
+            self.active
+                .iter()
+                .filter(move |b| oldest <= b.index && b.index <= index)
+        };
+
+        let mut summary = Summary::default();
+        let mut rate_sum = 0.;
+        let mut app_limited = 0;
+        let mut histogram = BTreeMap::new();
+        for bucket in in_window() {
+            summary.samples += bucket.samples;
+            summary.delivered += bucket.delivered;
+            rate_sum += bucket.rate_sum;
+            app_limited += bucket.app_limited;
+            summary.max_rate = Some(summary.max_rate.unwrap_or(0.).max(bucket.max_rate));
+            for (bin, count) in &bucket.histogram {
+                *histogram.entry(*bin).or_insert(0) += count;
+            }
+        }
+        if summary.samples == 0 {
+            return Summary::default();
+        }
+        let n = summary.samples as f64;
+        summary.mean_rate = Some(rate_sum / n);
+        summary.app_limited_fraction = Some(app_limited as f64 / n);
+
+        // Nearest rank
+        let rank = (0.95 * n).ceil() as u64;
+        let mut seen = 0;
+        for (bin, count) in histogram {
+            seen += count;
+            if rank <= seen {
+                summary.p95_rate = Some(bin_rate(bin));
+                break;
+            }
+        }
+        summary
+    }
+
+    fn bucket_index(&self, origin: Instant, now: Instant) -> u64 {
+        let elapsed = now.saturating_duration_since(origin);
+        (elapsed.as_nanos() / self.bucket_width.as_nanos().max(1)) as u64
+    }
+
+    fn expire(&mut self, index: u64) {
+        let oldest = (index + 1).saturating_sub(self.buckets as u64);
+        while self.active.front().is_some_and(|b| b.index < oldest) {
+            self.active.pop_front();
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Bucket {
+    index: u64,
+    samples: u64,
+    rate_sum: f64,
+    max_rate: f64,
+    app_limited: u64,
+    delivered: u64,
+    /// Histogram bin to sample count
+    histogram: BTreeMap<i32, u64>,
+}
+impl Bucket {
+    fn new(index: u64) -> Self {
+        Self {
+            index,
+            samples: 0,
+            rate_sum: 0.,
+            max_rate: 0.,
+            app_limited: 0,
+            delivered: 0,
+            histogram: BTreeMap::new(),
+        }
+    }
+}
+
+fn bin(rate: f64) -> i32 {
+    if rate <= 0. {
+        return i32::MIN;
+    }
+    (rate.log2() * BINS_PER_OCTAVE).floor() as i32
+}
+
+/// The geometric middle of the bin
+fn bin_rate(bin: i32) -> f64 {
+    if bin == i32::MIN {
+        return 0.;
+    }
+    ((bin as f64 + 0.5) / BINS_PER_OCTAVE).exp2()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_against_exact() {
+        let start = Instant::now();
+        let step = Duration::from_millis(100);
+        let s = Duration::from_secs(1);
+        let mut aggregator = RateSampleAggregator::new(10 * s, 10);
+        let mut samples = vec![];
+        // Linear congruential generator
+        let mut seed: u64 = 1;
+        for i in 0..300 {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            let rate = 1e5 + (seed >> 33) as f64 % 1e6;
+            let now = start + i * step;
+            let rs = RateSample::synthetic(rate, i % 4 == 0, now, step);
+            aggregator.push(&rs, now);
+            samples.push((now, rs));
+
+            // On a bucket boundary and in the middle of a bucket
+            if i != 250 && i != 277 {
+                continue;
+            }
+            // The bucket `now` falls into and the 9 preceding ones
+            let covered_since = start + (now - start).as_secs() as u32 * s - 9 * s;
+            let exact: Vec<_> = samples
+                .iter()
+                .filter(|(t, _)| covered_since <= *t)
+                .map(|(_, rs)| rs)
+                .collect();
+            let summary = aggregator.summary(now);
+            let n = exact.len() as f64;
+            assert_eq!(summary.samples, exact.len() as u64);
+            assert_eq!(
+                summary.delivered,
+                exact.iter().map(|rs| rs.delivered()).sum::<u64>()
+            );
+            let mean = exact.iter().map(|rs| rs.delivery_rate_raw()).sum::<f64>() / n;
+            assert!((summary.mean_rate.unwrap() - mean).abs() < 1e-6 * mean);
+            let max = exact
+                .iter()
+                .map(|rs| rs.delivery_rate_raw())
+                .fold(0., f64::max);
+            assert_eq!(summary.max_rate, Some(max));
+            let app_limited = exact.iter().filter(|rs| rs.is_app_limited()).count() as f64;
+            assert_eq!(summary.app_limited_fraction, Some(app_limited / n));
+
+            let mut rates: Vec<_> = exact.iter().map(|rs| rs.delivery_rate_raw()).collect();
+            rates.sort_by(f64::total_cmp);
+            let p95 = rates[(0.95 * n).ceil() as usize - 1];
+            let error = (summary.p95_rate.unwrap() - p95).abs() / p95;
+            assert!(error < 0.025, "{} {p95}", summary.p95_rate.unwrap());
+        }
+    }
+
+    #[test]
+    fn test_empty_window() {
+        let now = Instant::now();
+        let s = Duration::from_secs(1);
+        let mut aggregator = RateSampleAggregator::new(10 * s, 10);
+        assert_eq!(aggregator.summary(now), Summary::default());
+
+        aggregator.push(&RateSample::synthetic(1e6, false, now, s), now);
+        assert_eq!(aggregator.summary(now).samples, 1);
+        // Still covered by the oldest bucket of the window
+        assert_eq!(aggregator.summary(now + 9 * s).samples, 1);
+        // Expired exactly on the boundary
+        assert_eq!(aggregator.summary(now + 10 * s), Summary::default());
+    }
+}
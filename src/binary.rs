@@ -0,0 +1,190 @@
+//! A compact binary codec for high-rate sample capture
+//!
+//! Each sample is a fixed-size little-endian record of [`RECORD_LEN`] bytes:
+//!
+//! | Offset | Type  | Field                                                  |
+//! |--------|-------|--------------------------------------------------------|
+//! | 0      | `f32` | [`RateSample::delivery_rate_raw()`]                    |
+//! | 4      | `u32` | [`RateSample::delivered()`], saturated                 |
+//! | 8      | `u32` | [`RateSample::interval()`] in microseconds, saturated  |
+//! | 12     | `u8`  | flags                                                  |
+//!
+//! Precision tradeoffs:
+//! - The rate as `f32` keeps 24 significant bits, a relative error of at most about 6e-8; rates beyond about 3.4e38 become infinite.
+//! - `delivered` saturates at about 4 GB (or 4 billion packets) per sample.
+//! - The interval is truncated to whole microseconds and saturates at about 71 minutes.
+//! - Time stamps (e.g. [`RateSample::prior_time()`]) are not recorded; the records keep the capture order.
+
+use std::{
+    io::{self, Read, Write},
+    time::Duration,
+};
+
+use crate::{PacketClass, RateSample};
+
+pub const RECORD_LEN: usize = 13;
+
+const APP_LIMITED: u8 = 1 << 0;
+const QUANTIZED: u8 = 1 << 1;
+const RELIABLE: u8 = 1 << 2;
+const SENT_IN_RECOVERY: u8 = 1 << 3;
+const ACKED_IN_RECOVERY: u8 = 1 << 4;
+const ACK_COMPRESSED: u8 = 1 << 5;
+const POST_RECOVERY: u8 = 1 << 6;
+const LOSS_PROBE: u8 = 1 << 7;
+
+/// A sample as decoded from a record
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BinarySample {
+    /// Measured in octets or packets per second
+    pub delivery_rate: f32,
+    /// Measured in octets or packets
+    pub delivered: u32,
+    pub interval: Duration,
+    pub is_app_limited: bool,
+    pub is_quantized: bool,
+    pub is_reliable: bool,
+    pub is_sent_in_recovery: bool,
+    pub is_acked_in_recovery: bool,
+    pub is_ack_compressed: bool,
+    pub is_post_recovery: bool,
+    /// [`RateSample::newest_packet_class()`] was [`PacketClass::LossProbe`]
+    pub is_loss_probe: bool,
+}
+
+pub fn write_sample<W: Write>(w: &mut W, sample: &RateSample) -> io::Result<()> {
+    let mut record = [0; RECORD_LEN];
+    record[0..4].copy_from_slice(&(sample.delivery_rate_raw() as f32).to_le_bytes());
+    let delivered = u32::try_from(sample.delivered()).unwrap_or(u32::MAX);
+    record[4..8].copy_from_slice(&delivered.to_le_bytes());
+    let interval = u32::try_from(sample.interval().as_micros()).unwrap_or(u32::MAX);
+    record[8..12].copy_from_slice(&interval.to_le_bytes());
+    let flags = [
+        (sample.is_app_limited(), APP_LIMITED),
+        (sample.is_quantized(), QUANTIZED),
+        (sample.is_reliable(), RELIABLE),
+        (sample.is_sent_in_recovery(), SENT_IN_RECOVERY),
+        (sample.is_acked_in_recovery(), ACKED_IN_RECOVERY),
+        (sample.is_ack_compressed(), ACK_COMPRESSED),
+        (sample.is_post_recovery(), POST_RECOVERY),
+        (
+            sample.newest_packet_class() == PacketClass::LossProbe,
+            LOSS_PROBE,
+        ),
+    ];
+    record[12] = flags
+        .into_iter()
+        .filter(|(set, _)| *set)
+        .fold(0, |flags, (_, bit)| flags | bit);
+    w.write_all(&record)
+}
+
+/// Read the next record
+///
+/// [`None`] at the end of the stream; a partial record is an [`io::ErrorKind::UnexpectedEof`] error.
+pub fn read_sample<R: Read>(r: &mut R) -> io::Result<Option<BinarySample>> {
+    let mut record = [0; RECORD_LEN];
+    let mut filled = 0;
+    while filled < RECORD_LEN {
+        match r.read(&mut record[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    let field = |i: usize| -> [u8; 4] { record[i..i + 4].try_into().unwrap() };
+    let flags = record[12];
+    let flag = |bit: u8| flags & bit != 0;
+    Ok(Some(BinarySample {
+        delivery_rate: f32::from_le_bytes(field(0)),
+        delivered: u32::from_le_bytes(field(4)),
+        interval: Duration::from_micros(u32::from_le_bytes(field(8)).into()),
+        is_app_limited: flag(APP_LIMITED),
+        is_quantized: flag(QUANTIZED),
+        is_reliable: flag(RELIABLE),
+        is_sent_in_recovery: flag(SENT_IN_RECOVERY),
+        is_acked_in_recovery: flag(ACKED_IN_RECOVERY),
+        is_ack_compressed: flag(ACK_COMPRESSED),
+        is_post_recovery: flag(POST_RECOVERY),
+        is_loss_probe: flag(LOSS_PROBE),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::test_util::RateSampleBuilder;
+
+    #[test]
+    fn test_round_trip() {
+        let now = Instant::now();
+        let samples: Vec<_> = (0..1_000_u64)
+            .map(|i| {
+                let class = if i % 7 == 0 {
+                    PacketClass::LossProbe
+                } else {
+                    PacketClass::Data
+                };
+                RateSampleBuilder::new(now)
+                    .delivery_rate(i as f64 * 12_345.678)
+                    .delivered(i * 1_200)
+                    .interval(Duration::from_nanos(i * 1_234_567))
+                    .is_app_limited(i % 2 == 0)
+                    .is_quantized(i % 3 == 0)
+                    .is_reliable(i % 5 != 0)
+                    .is_sent_in_recovery(i % 11 == 0)
+                    .is_acked_in_recovery(i % 13 == 0)
+                    .is_ack_compressed(i % 17 == 0)
+                    .is_post_recovery(i % 19 == 0)
+                    .newest_packet_class(class)
+                    .build()
+            })
+            .collect();
+        let mut buf = vec![];
+        for rs in &samples {
+            write_sample(&mut buf, rs).unwrap();
+        }
+        assert_eq!(buf.len(), samples.len() * RECORD_LEN);
+
+        let mut r = &buf[..];
+        for rs in &samples {
+            let decoded = read_sample(&mut r).unwrap().unwrap();
+            let rate = rs.delivery_rate_raw();
+            assert!((decoded.delivery_rate as f64 - rate).abs() <= rate * 1e-7);
+            assert_eq!(decoded.delivered as u64, rs.delivered());
+            assert_eq!(decoded.interval.as_micros(), rs.interval().as_micros());
+            assert_eq!(decoded.is_app_limited, rs.is_app_limited());
+            assert_eq!(decoded.is_quantized, rs.is_quantized());
+            assert_eq!(decoded.is_reliable, rs.is_reliable());
+            assert_eq!(decoded.is_sent_in_recovery, rs.is_sent_in_recovery());
+            assert_eq!(decoded.is_acked_in_recovery, rs.is_acked_in_recovery());
+            assert_eq!(decoded.is_ack_compressed, rs.is_ack_compressed());
+            assert_eq!(decoded.is_post_recovery, rs.is_post_recovery());
+            assert_eq!(
+                decoded.is_loss_probe,
+                rs.newest_packet_class() == PacketClass::LossProbe
+            );
+        }
+        assert_eq!(read_sample(&mut r).unwrap(), None);
+    }
+
+    #[test]
+    fn test_saturation_and_truncation() {
+        let rs = RateSampleBuilder::new(Instant::now())
+            .delivered(u64::MAX)
+            .interval(Duration::from_secs(1 << 40))
+            .build();
+        let mut buf = vec![];
+        write_sample(&mut buf, &rs).unwrap();
+        let decoded = read_sample(&mut &buf[..]).unwrap().unwrap();
+        assert_eq!(decoded.delivered, u32::MAX);
+        assert_eq!(decoded.interval, Duration::from_micros(u32::MAX.into()));
+
+        let e = read_sample(&mut &buf[..RECORD_LEN - 1]).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
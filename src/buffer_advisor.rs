@@ -0,0 +1,285 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::{DeliveryRate, RateSample};
+
+#[derive(Debug, Clone)]
+pub struct BufferAdvisorConfig {
+    /// The max BDP is taken over the samples within this window
+    pub window: Duration,
+    /// The recommendation is this multiple of the max BDP
+    pub headroom_factor: f64,
+    /// Measured in octets or packets
+    ///
+    /// Wins over [`Self::max`] if they cross.
+    pub min: u64,
+    /// Measured in octets or packets
+    pub max: u64,
+    /// The recommendation only moves once the target differs from it by more than this fraction
+    pub hysteresis: f64,
+}
+impl Default for BufferAdvisorConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(10),
+            headroom_factor: 2.,
+            min: 64 * 1024,
+            max: 16 * 1024 * 1024,
+            hysteresis: 0.25,
+        }
+    }
+}
+
+/// Buffer size recommendation from the BDP history, like Linux's receive buffer autotuning
+///
+/// Agnostic to the side of the connection: the samples may come from the sender or from a receiver measuring its own delivery rate.
+#[derive(Debug, Clone)]
+pub struct BufferAdvisor {
+    config: BufferAdvisorConfig,
+    /// `(sampled at, BDP)`, with decreasing BDPs for the sliding window max
+    bdps: VecDeque<(Instant, u64)>,
+    recommendation: u64,
+}
+impl BufferAdvisor {
+    pub fn new(config: BufferAdvisorConfig) -> Self {
+        let recommendation = config.min;
+        Self {
+            config,
+            bdps: VecDeque::new(),
+            recommendation,
+        }
+    }
+
+    /// Upon a rate sample being produced
+    ///
+    /// Unreliable samples are ignored.
+    /// Return the recommendation.
+    pub fn on_sample(&mut self, sample: &RateSample, min_rtt: Duration, now: Instant) -> u64 {
+        self.expire(now);
+        if !sample.is_reliable() {
+            return self.recommendation;
+        }
+        let bdp = sample.bdp(min_rtt);
+        while self.bdps.back().is_some_and(|(_, b)| *b <= bdp) {
+            self.bdps.pop_back();
+        }
+        self.bdps.push_back((now, bdp));
+
+        let target = (self.max_bdp().unwrap() as f64 * self.config.headroom_factor) as u64;
+        let target = target.min(self.config.max).max(self.config.min);
+        let current = self.recommendation as f64;
+        let band = current * self.config.hysteresis;
+        if (target as f64 - current).abs() > band {
+            self.recommendation = target;
+        }
+        self.recommendation
+    }
+
+    /// The max BDP within the window, measured in octets or packets
+    pub fn max_bdp(&self) -> Option<u64> {
+        self.bdps.front().map(|(_, bdp)| *bdp)
+    }
+
+    /// The recommended buffer size, measured in octets or packets
+    ///
+    /// [`BufferAdvisorConfig::min`] before any sample.
+    pub fn recommendation(&self) -> u64 {
+        self.recommendation
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while self
+            .bdps
+            .front()
+            .is_some_and(|(t, _)| self.config.window <= now.saturating_duration_since(*t))
+        {
+            self.bdps.pop_front();
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SendBufferConfig {
+    /// The target holds this multiple of the BDP
+    pub bdp_multiplier: f64,
+    /// Measured in octets
+    ///
+    /// Wins over [`Self::ceiling`] if they cross.
+    pub floor: u64,
+    /// Measured in octets
+    pub ceiling: u64,
+}
+impl Default for SendBufferConfig {
+    fn default() -> Self {
+        Self {
+            bdp_multiplier: 2.,
+            floor: 64 * 1024,
+            ceiling: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// The send buffer size (measured in octets) that keeps the pipe full: `bdp_multiplier` × BDP plus the retransmission backlog, clamped to the floor and the ceiling
+///
+/// `bw`: [`None`] if the bandwidth is still unmeasured, in which case the floor is returned.
+pub fn send_buffer_target(
+    bw: Option<DeliveryRate>,
+    min_rtt: Duration,
+    retrans_backlog_bytes: u64,
+    config: &SendBufferConfig,
+) -> u64 {
+    let Some(bw) = bw else {
+        return config.floor;
+    };
+    let bdp = bw.as_bytes_per_sec() * min_rtt.as_secs_f64();
+    let target = ((bdp * config.bdp_multiplier) as u64).saturating_add(retrans_backlog_bytes);
+    target.min(config.ceiling).max(config.floor)
+}
+
+/// [`send_buffer_target()`] smoothed over time, so a transient spike (e.g. in RTT) barely moves it
+#[derive(Debug, Clone)]
+pub struct SendBufferTarget {
+    config: SendBufferConfig,
+    /// The weight of each new target in the moving average
+    gain: f64,
+    smoothed: Option<f64>,
+}
+impl SendBufferTarget {
+    /// `gain`: the weight of each new target in the exponentially weighted moving average, e.g. `1/8`
+    pub fn new(config: SendBufferConfig, gain: f64) -> Self {
+        Self {
+            config,
+            gain,
+            smoothed: None,
+        }
+    }
+
+    /// Return the smoothed target
+    ///
+    /// While the bandwidth is unmeasured, the smoothed target is left untouched.
+    pub fn update(
+        &mut self,
+        bw: Option<DeliveryRate>,
+        min_rtt: Duration,
+        retrans_backlog_bytes: u64,
+    ) -> u64 {
+        if bw.is_some() {
+            let target =
+                send_buffer_target(bw, min_rtt, retrans_backlog_bytes, &self.config) as f64;
+            self.smoothed = Some(match self.smoothed {
+                Some(smoothed) => smoothed + self.gain * (target - smoothed),
+                None => target,
+            });
+        }
+        self.target()
+    }
+
+    /// Measured in octets; the floor before the bandwidth is measured
+    pub fn target(&self) -> u64 {
+        self.smoothed
+            .map_or(self.config.floor, |smoothed| smoothed as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_doubling_steps_once() {
+        let now = Instant::now();
+        let ms = Duration::from_millis(1);
+        let min_rtt = 100 * ms;
+        let mut advisor = BufferAdvisor::new(BufferAdvisorConfig::default());
+        let mut recommendations = vec![advisor.recommendation()];
+        for i in 0..200_u32 {
+            let rate = if i < 100 { 1e6 } else { 2e6 };
+            // ±4% noise
+            let noise = [0.96, 1., 1.04][i as usize % 3];
+            let t = now + i * 10 * ms;
+            let rs = RateSample::synthetic(rate * noise, false, t, min_rtt);
+            let recommendation = advisor.on_sample(&rs, min_rtt, t + min_rtt);
+            if recommendations.last() != Some(&recommendation) {
+                recommendations.push(recommendation);
+            }
+        }
+        assert_eq!(recommendations, [64 * 1024, 192_000, 400_000]);
+        assert_eq!(advisor.max_bdp(), Some(208_000));
+    }
+
+    #[test]
+    fn test_window_and_clamp() {
+        let now = Instant::now();
+        let s = Duration::from_secs(1);
+        let config = BufferAdvisorConfig {
+            max: 1_000_000,
+            ..Default::default()
+        };
+        let mut advisor = BufferAdvisor::new(config);
+        let rs = RateSample::synthetic(1e8, false, now, s);
+        assert_eq!(advisor.on_sample(&rs, s, now), 1_000_000);
+        assert_eq!(advisor.max_bdp(), Some(100_000_000));
+
+        // The large sample left the window
+        let rs = RateSample::synthetic(1e5, false, now, s);
+        assert_eq!(advisor.on_sample(&rs, s, now + 10 * s), 200_000);
+        assert_eq!(advisor.max_bdp(), Some(100_000));
+
+        // Crossed bounds
+        let config = BufferAdvisorConfig {
+            min: 1_000_000,
+            max: 1_000,
+            ..Default::default()
+        };
+        let mut advisor = BufferAdvisor::new(config);
+        assert_eq!(advisor.on_sample(&rs, s, now), 1_000_000);
+    }
+
+    #[test]
+    fn test_send_buffer_target_clamp() {
+        let config = SendBufferConfig::default();
+        let ms = Duration::from_millis(1);
+        let bw = |bytes_per_sec| Some(DeliveryRate::from_bytes_per_sec(bytes_per_sec));
+        assert_eq!(send_buffer_target(None, 100 * ms, 0, &config), 64 * 1024);
+        // Below the floor
+        assert_eq!(send_buffer_target(bw(1e5), 100 * ms, 0, &config), 64 * 1024);
+        // Just above the floor, including the backlog
+        assert_eq!(
+            send_buffer_target(bw(1e5), 100 * ms, 50_000, &config),
+            70_000
+        );
+        assert_eq!(send_buffer_target(bw(1e7), 100 * ms, 0, &config), 2_000_000);
+        // Above the ceiling
+        assert_eq!(
+            send_buffer_target(bw(1e9), 100 * ms, 0, &config),
+            16 * 1024 * 1024
+        );
+        assert_eq!(
+            send_buffer_target(bw(1e9), 100 * ms, u64::MAX, &config),
+            16 * 1024 * 1024
+        );
+
+        // Crossed bounds
+        let config = SendBufferConfig {
+            floor: 1_000_000,
+            ceiling: 1_000,
+            ..Default::default()
+        };
+        assert_eq!(send_buffer_target(bw(1e9), 100 * ms, 0, &config), 1_000_000);
+    }
+
+    #[test]
+    fn test_send_buffer_target_smoothed() {
+        let ms = Duration::from_millis(1);
+        let bw = Some(DeliveryRate::from_bytes_per_sec(1e7));
+        let mut target = SendBufferTarget::new(SendBufferConfig::default(), 1. / 8.);
+        assert_eq!(target.update(None, 100 * ms, 0), 64 * 1024);
+        assert_eq!(target.update(bw, 100 * ms, 0), 2_000_000);
+        // A transient RTT spike
+        assert_eq!(target.update(bw, 200 * ms, 0), 2_250_000);
+        assert_eq!(target.update(bw, 100 * ms, 0), 2_218_750);
+        assert_eq!(target.update(None, 100 * ms, 0), 2_218_750);
+    }
+}
@@ -0,0 +1,123 @@
+//! Sources of the [`Instant`]s fed to [`ConnectionState`](crate::ConnectionState)
+//!
+//! Every timestamp this crate takes is a [`std::time::Instant`], so a clock only has to produce one; nothing else changes with the clock.
+
+use std::time::Instant;
+
+/// Where the caller takes its send and `ACK` timestamps from
+pub trait Clock: std::fmt::Debug + Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+/// [`Instant::now()`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdClock;
+impl Clock for StdClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A [`quanta::Clock`] reading the TSC, several times cheaper than [`Instant::now()`]'s `clock_gettime`
+///
+/// Readings are converted to [`Instant`]s through an anchor pairing a quanta reading with an [`Instant`] taken at the same moment.
+///
+/// Accuracy trade-offs:
+/// - quanta calibrates the TSC frequency against the OS clock once, in [`quanta::Clock::new()`], which takes a few milliseconds; a calibration error of a few ppm stretches or shrinks every interval by as much.
+/// - Against [`Instant`], that error accumulates as drift; [`Self::recalibrate()`] re-anchors to bound it for long-lived clocks.
+/// - Without an invariant TSC (e.g. some VMs), quanta falls back to the OS clock, which is as accurate as [`StdClock`] but no cheaper.
+#[cfg(feature = "quanta")]
+#[derive(Debug, Clone)]
+pub struct QuantaClock {
+    clock: quanta::Clock,
+    anchor: (Instant, quanta::Instant),
+}
+#[cfg(feature = "quanta")]
+impl QuantaClock {
+    /// Calibrates a new [`quanta::Clock`]
+    pub fn new() -> Self {
+        Self::with_clock(quanta::Clock::new())
+    }
+
+    pub fn with_clock(clock: quanta::Clock) -> Self {
+        let anchor = (Instant::now(), clock.now());
+        Self { clock, anchor }
+    }
+
+    /// Re-anchor to the current [`Instant`], discarding the drift accumulated since the last anchor
+    ///
+    /// Readings never go backwards across a recalibration: if the TSC ran ahead of [`Instant`], the converted time holds until [`Instant`] catches up.
+    pub fn recalibrate(&mut self) {
+        let now = self.now();
+        self.anchor = (Instant::now().max(now), self.clock.now());
+    }
+}
+#[cfg(feature = "quanta")]
+impl Default for QuantaClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+#[cfg(feature = "quanta")]
+impl Clock for QuantaClock {
+    fn now(&self) -> Instant {
+        let (instant, reading) = self.anchor;
+        instant + self.clock.now().saturating_duration_since(reading)
+    }
+}
+
+#[cfg(all(test, feature = "quanta"))]
+mod tests {
+    use std::time::Duration;
+
+    use crate::ConnectionState;
+
+    use super::*;
+
+    #[test]
+    fn test_quanta_conversion() {
+        let (clock, mock) = quanta::Clock::mock();
+        let mut c = QuantaClock::with_clock(clock);
+        let t0 = c.now();
+        mock.increment(Duration::from_millis(5));
+        assert_eq!(c.now() - t0, Duration::from_millis(5));
+        // The mock does not advance; the converted time holds rather than going backwards
+        c.recalibrate();
+        assert!(c.now() - t0 >= Duration::from_millis(5));
+    }
+
+    #[test]
+    fn test_quanta_agrees_with_std() {
+        // Both clocks advance in lockstep, so no scheduling delay between their readings
+        let (mock_clock, mock) = quanta::Clock::mock();
+        let quanta = QuantaClock::with_clock(mock_clock);
+        let mut std_now = Instant::now();
+        let mut states = [std_now, quanta.now()].map(ConnectionState::new);
+        let mut rates = vec![];
+        for _ in 0..3 {
+            let sent: Vec<_> = states
+                .iter_mut()
+                .zip([std_now, quanta.now()])
+                .map(|(c, now)| {
+                    (0..10)
+                        .map(|_| (c.send_packet_2(now, false), 1_200))
+                        .collect::<Vec<_>>()
+                })
+                .collect();
+            std_now += Duration::from_millis(20);
+            mock.increment(Duration::from_millis(20));
+            let samples: Vec<_> = states
+                .iter_mut()
+                .zip([std_now, quanta.now()])
+                .zip(sent)
+                .map(|((c, now), sent)| c.sample_rate_iter(sent, now, Duration::ZERO))
+                .collect();
+            rates.push(samples);
+        }
+        for samples in &rates[1..] {
+            let [std, quanta] = [&samples[0], &samples[1]]
+                .map(|s| s.as_ref().unwrap().delivery_rate().as_bytes_per_sec());
+            assert!((std - quanta).abs() / std < 1e-9, "{std} {quanta}");
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+use crate::{DeliveryRate, RateSample};
+
+/// A bandwidth to pace the first round with before any rate sample exists
+///
+/// Transitional: the bootstrap `initial_cwnd / min_rtt` only stands in until the first real sample arrives.
+/// From then on the largest delivery rate sampled so far is reported instead.
+#[derive(Debug, Clone)]
+pub struct ColdStartBandwidth {
+    /// Measured in octets or packets
+    initial_cwnd: u64,
+    measured: Option<DeliveryRate>,
+}
+impl ColdStartBandwidth {
+    /// `initial_cwnd`: measured in octets or packets
+    pub fn new(initial_cwnd: u64) -> Self {
+        Self {
+            initial_cwnd,
+            measured: None,
+        }
+    }
+
+    /// Unreliable samples are ignored
+    pub fn on_sample(&mut self, sample: &RateSample) {
+        if !sample.is_reliable() {
+            return;
+        }
+        let rate = sample.delivery_rate();
+        if self.measured.is_none_or(|measured| measured < rate) {
+            self.measured = Some(rate);
+        }
+    }
+
+    /// Either:
+    /// - The largest sampled delivery rate,
+    /// - or the bootstrap `initial_cwnd / min_rtt` if nothing is sampled yet.
+    ///
+    /// `min_rtt`: the RTT measured in the first round; [`None`] if no RTT is measured yet.
+    pub fn bandwidth(&self, min_rtt: Option<Duration>) -> Option<DeliveryRate> {
+        if let Some(measured) = self.measured {
+            return Some(measured);
+        }
+        let min_rtt = min_rtt.filter(|min_rtt| !min_rtt.is_zero())?;
+        Some(DeliveryRate::from_bytes_per_sec(
+            self.initial_cwnd as f64 / min_rtt.as_secs_f64(),
+        ))
+    }
+
+    /// Whether [`Self::bandwidth()`] still reports the bootstrap
+    pub fn is_bootstrap(&self) -> bool {
+        self.measured.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::{ConnectionState, Packet};
+
+    #[test]
+    fn test_bootstrap_replaced() {
+        let now = Instant::now();
+        let min_rtt = Duration::from_millis(100);
+        let mut cs = ColdStartBandwidth::new(10_000);
+        assert_eq!(cs.bandwidth(None), None);
+        assert_eq!(
+            cs.bandwidth(Some(min_rtt)).unwrap().as_bytes_per_sec(),
+            100_000.
+        );
+        assert!(cs.is_bootstrap());
+
+        let mut c = ConnectionState::new(now);
+        let mut now = now;
+        for _ in 0..2 {
+            let p = c.send_packet_2(now, true);
+            now += min_rtt;
+            let rs = c.sample_rate(
+                &[Packet {
+                    state: p,
+                    data_length: 1_000,
+                }],
+                now,
+                min_rtt,
+            );
+            if let Some(rs) = rs {
+                cs.on_sample(&rs);
+            }
+        }
+        assert!(!cs.is_bootstrap());
+        assert_eq!(
+            cs.bandwidth(Some(min_rtt)).unwrap().as_bytes_per_sec(),
+            10_000.
+        );
+    }
+}
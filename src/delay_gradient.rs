@@ -0,0 +1,190 @@
+use std::time::{Duration, Instant};
+
+use crate::RateSample;
+
+#[derive(Debug, Clone)]
+pub struct DelayGradientConfig {
+    /// The weight of a new per-round gradient in the exponentially weighted moving average
+    pub gain: f64,
+    /// A smoothed min-RTT gradient within `±threshold` per round is [`DelayTrend::Stable`]
+    pub threshold: Duration,
+}
+impl Default for DelayGradientConfig {
+    fn default() -> Self {
+        Self {
+            gain: 0.25,
+            threshold: Duration::from_millis(1),
+        }
+    }
+}
+
+/// What the smoothed min-RTT gradient says about the bottleneck queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DelayTrend {
+    QueueBuilding,
+    Draining,
+    Stable,
+}
+
+/// The RTT extremes of a round
+#[derive(Debug, Clone, Copy)]
+struct Round {
+    min: Duration,
+    max: Duration,
+    end: Instant,
+}
+
+/// CDG-style delay gradients: the change of the min and max RTT per round, smoothed across rounds
+///
+/// Rounds are counted from the rate samples the way BBR does: a round ends when a sample's [`RateSample::prior_delivered()`] reaches the data delivered at the end of the previous round.
+///
+/// Rounds without samples (e.g. while idle) are not observed, so the RTT change between two observed rounds is spread over the number of min RTTs between them instead of being taken as a one-round spike.
+#[derive(Debug, Clone)]
+pub struct DelayGradient {
+    config: DelayGradientConfig,
+    next_round_delivered: u64,
+    /// The round in progress; `end` is the [`RateSample::prior_time()`] of its latest sample
+    current: Option<Round>,
+    prev: Option<Round>,
+    /// Measured in seconds per round
+    min_gradient: Option<f64>,
+    /// Measured in seconds per round
+    max_gradient: Option<f64>,
+}
+impl DelayGradient {
+    pub fn new(config: DelayGradientConfig) -> Self {
+        Self {
+            config,
+            next_round_delivered: 0,
+            current: None,
+            prev: None,
+            min_gradient: None,
+            max_gradient: None,
+        }
+    }
+
+    /// `rtt`: the RTT measured on the `ACK` that produced `sample`
+    pub fn on_sample(&mut self, sample: &RateSample, rtt: Duration) {
+        if self.next_round_delivered <= sample.prior_delivered() {
+            if let Some(round) = self.current.take() {
+                self.close_round(round);
+            }
+            self.next_round_delivered = sample.prior_delivered() + sample.delivered();
+        }
+        let round = self.current.get_or_insert(Round {
+            min: rtt,
+            max: rtt,
+            end: sample.prior_time(),
+        });
+        round.min = round.min.min(rtt);
+        round.max = round.max.max(rtt);
+        round.end = round.end.max(sample.prior_time());
+    }
+
+    fn close_round(&mut self, round: Round) {
+        let Some(prev) = self.prev.replace(round) else {
+            return;
+        };
+        let rounds = if prev.min.is_zero() {
+            1.
+        } else {
+            let elapsed = round.end.saturating_duration_since(prev.end);
+            (elapsed.as_secs_f64() / prev.min.as_secs_f64())
+                .round()
+                .max(1.)
+        };
+        let gradient =
+            |new: Duration, old: Duration| (new.as_secs_f64() - old.as_secs_f64()) / rounds;
+        let smooth = |smoothed: Option<f64>, gradient: f64| {
+            Some(smoothed.map_or(gradient, |smoothed| {
+                smoothed + self.config.gain * (gradient - smoothed)
+            }))
+        };
+        self.min_gradient = smooth(self.min_gradient, gradient(round.min, prev.min));
+        self.max_gradient = smooth(self.max_gradient, gradient(round.max, prev.max));
+    }
+
+    /// The smoothed change of the per-round min RTT, measured in seconds per round; [`None`] until two rounds have closed
+    pub fn min_gradient(&self) -> Option<f64> {
+        self.min_gradient
+    }
+
+    /// The smoothed change of the per-round max RTT, measured in seconds per round; [`None`] until two rounds have closed
+    pub fn max_gradient(&self) -> Option<f64> {
+        self.max_gradient
+    }
+
+    /// Classified from the min-RTT gradient; [`None`] until two rounds have closed
+    pub fn trend(&self) -> Option<DelayTrend> {
+        let gradient = self.min_gradient?;
+        let threshold = self.config.threshold.as_secs_f64();
+        Some(if threshold < gradient {
+            DelayTrend::QueueBuilding
+        } else if gradient < -threshold {
+            DelayTrend::Draining
+        } else {
+            DelayTrend::Stable
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::RateSampleBuilder;
+
+    /// Feed a round of two samples, reported as if the round began at `start`
+    fn feed_round(dg: &mut DelayGradient, round: u64, start: Instant, rtt: Duration) {
+        for (offset, jitter) in [(0, 0), (5, 3)] {
+            let sample = RateSampleBuilder::new(start)
+                .prior_delivered(round * 10 + offset)
+                .delivered(10)
+                .build();
+            dg.on_sample(&sample, rtt + Duration::from_millis(jitter));
+        }
+    }
+
+    /// Round trips of `rtts`, each lasting its RTT; return the start of the next round
+    fn feed(dg: &mut DelayGradient, round: &mut u64, mut now: Instant, rtts: &[u64]) -> Instant {
+        for rtt in rtts {
+            let rtt = Duration::from_millis(*rtt);
+            feed_round(dg, *round, now, rtt);
+            *round += 1;
+            now += rtt;
+        }
+        now
+    }
+
+    #[test]
+    fn test_ramp_and_drain() {
+        let mut dg = DelayGradient::new(DelayGradientConfig::default());
+        let mut round = 0;
+        let now = Instant::now();
+        let ramp: Vec<u64> = (0..10).map(|k| 50 + 4 * k).collect();
+        let now = feed(&mut dg, &mut round, now, &ramp);
+        assert_eq!(dg.trend(), Some(DelayTrend::QueueBuilding));
+        let ms = 1e-3;
+        assert!((dg.min_gradient().unwrap() - 4. * ms).abs() < 0.5 * ms);
+        assert!((dg.max_gradient().unwrap() - 4. * ms).abs() < 0.5 * ms);
+
+        let drain: Vec<u64> = (0..10).map(|k| 86 - 4 * k).collect();
+        feed(&mut dg, &mut round, now, &drain);
+        assert_eq!(dg.trend(), Some(DelayTrend::Draining));
+    }
+
+    #[test]
+    fn test_missing_rounds() {
+        let mut dg = DelayGradient::new(DelayGradientConfig::default());
+        let mut round = 0;
+        let now = Instant::now();
+        let now = feed(&mut dg, &mut round, now, &[50; 10]);
+        assert_eq!(dg.trend(), Some(DelayTrend::Stable));
+
+        // Ten rounds pass unobserved while the RTT rises by 5 ms
+        let now = now + Duration::from_millis(500);
+        round += 10;
+        feed(&mut dg, &mut round, now, &[55; 2]);
+        assert_eq!(dg.trend(), Some(DelayTrend::Stable));
+        assert!(dg.min_gradient().unwrap() < 0.5e-3);
+    }
+}
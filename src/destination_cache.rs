@@ -0,0 +1,260 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// An opaque destination identifier
+///
+/// The caller hashes whatever identifies the destination (an address, a prefix, ...) into it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DestKey(pub u64);
+
+/// The "last known good" estimates of a destination
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CachedEstimate {
+    /// The final filtered bandwidth
+    ///
+    /// Measured in octets or packets per second
+    pub bandwidth: f64,
+    /// The minimum round-trip time
+    pub min_rtt: Duration,
+    /// The time when the estimates were stored
+    pub updated_at: Instant,
+}
+
+/// The estimates a connection offers to the cache
+#[derive(Debug, Clone, Copy)]
+pub struct EstimateUpdate {
+    /// The final filtered bandwidth
+    ///
+    /// Measured in octets or packets per second
+    pub bandwidth: f64,
+    /// The minimum round-trip time
+    pub min_rtt: Duration,
+    /// Whether the bandwidth came from application-limited samples only
+    pub is_app_limited: bool,
+    /// Whether the filters of the connection have converged
+    pub converged: bool,
+}
+
+/// Which offered estimates are allowed to overwrite a cached entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdatePolicy {
+    /// Every offered estimate overwrites the entry
+    Always,
+    /// Only non-application-limited, converged estimates overwrite the entry
+    MeasuredOnly,
+}
+impl UpdatePolicy {
+    fn accepts(&self, update: &EstimateUpdate) -> bool {
+        match self {
+            UpdatePolicy::Always => true,
+            UpdatePolicy::MeasuredOnly => !update.is_app_limited && update.converged,
+        }
+    }
+}
+
+/// Tunables of a [`DestinationCache`]
+#[derive(Debug, Clone)]
+pub struct DestinationCacheConfig {
+    /// The maximum number of destinations remembered
+    ///
+    /// The least recently used destination is evicted once exceeded.
+    pub capacity: usize,
+    /// Entries older than this are considered stale and are never returned
+    pub max_age: Duration,
+    pub policy: UpdatePolicy,
+}
+
+/// A cache of bandwidth and min-RTT estimates per destination, similar to the TCP metrics cache of the kernel
+///
+/// It is safe to share among connections on different threads.
+#[derive(Debug)]
+pub struct DestinationCache {
+    config: DestinationCacheConfig,
+    lru: Mutex<Lru>,
+}
+impl DestinationCache {
+    pub fn new(config: DestinationCacheConfig) -> Self {
+        Self {
+            config,
+            lru: Mutex::new(Lru::default()),
+        }
+    }
+
+    /// Return the estimates of `key` if they are not stale
+    ///
+    /// Stale entries are removed.
+    pub fn get(&self, key: DestKey, now: Instant) -> Option<CachedEstimate> {
+        let mut lru = self.lru.lock().unwrap();
+        let estimate = lru.get(key)?;
+        if self.is_stale(&estimate, now) {
+            lru.remove(key);
+            return None;
+        }
+        Some(estimate)
+    }
+
+    /// Offer the estimates of a connection to `key`
+    ///
+    /// Return whether the cache was updated.
+    /// - Estimates rejected by [`DestinationCacheConfig::policy`] do not overwrite a fresh entry.
+    /// - They are still stored if the destination is unknown or its entry is stale.
+    pub fn update(&self, key: DestKey, update: EstimateUpdate, now: Instant) -> bool {
+        let mut lru = self.lru.lock().unwrap();
+        let fresh = lru
+            .peek(key)
+            .is_some_and(|estimate| !self.is_stale(&estimate, now));
+        if fresh && !self.config.policy.accepts(&update) {
+            return false;
+        }
+        let estimate = CachedEstimate {
+            bandwidth: update.bandwidth,
+            min_rtt: update.min_rtt,
+            updated_at: now,
+        };
+        lru.insert(key, estimate, self.config.capacity);
+        true
+    }
+
+    pub fn remove(&self, key: DestKey) -> Option<CachedEstimate> {
+        self.lru.lock().unwrap().remove(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.lru.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn is_stale(&self, estimate: &CachedEstimate, now: Instant) -> bool {
+        self.config.max_age < now.saturating_duration_since(estimate.updated_at)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Lru {
+    /// Destination -> (estimate, last use tick)
+    entries: HashMap<DestKey, (CachedEstimate, u64)>,
+    /// Last use tick -> destination
+    recency: BTreeMap<u64, DestKey>,
+    next_tick: u64,
+}
+impl Lru {
+    fn peek(&self, key: DestKey) -> Option<CachedEstimate> {
+        self.entries.get(&key).map(|(estimate, _)| *estimate)
+    }
+
+    fn get(&mut self, key: DestKey) -> Option<CachedEstimate> {
+        let tick = self.tick();
+        let (estimate, last_use) = self.entries.get_mut(&key)?;
+        self.recency.remove(last_use);
+        *last_use = tick;
+        self.recency.insert(tick, key);
+        Some(*estimate)
+    }
+
+    fn insert(&mut self, key: DestKey, estimate: CachedEstimate, capacity: usize) {
+        self.remove(key);
+        if capacity == 0 {
+            return;
+        }
+        while capacity <= self.entries.len() {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+        let tick = self.tick();
+        self.entries.insert(key, (estimate, tick));
+        self.recency.insert(tick, key);
+    }
+
+    fn remove(&mut self, key: DestKey) -> Option<CachedEstimate> {
+        let (estimate, last_use) = self.entries.remove(&key)?;
+        self.recency.remove(&last_use);
+        Some(estimate)
+    }
+
+    fn tick(&mut self) -> u64 {
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        tick
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measured(bandwidth: f64) -> EstimateUpdate {
+        EstimateUpdate {
+            bandwidth,
+            min_rtt: Duration::from_millis(10),
+            is_app_limited: false,
+            converged: true,
+        }
+    }
+
+    fn cache(capacity: usize, policy: UpdatePolicy) -> DestinationCache {
+        DestinationCache::new(DestinationCacheConfig {
+            capacity,
+            max_age: Duration::from_secs(10),
+            policy,
+        })
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let now = Instant::now();
+        let c = cache(2, UpdatePolicy::Always);
+        c.update(DestKey(1), measured(1.), now);
+        c.update(DestKey(2), measured(2.), now);
+        // Touch 1 so that 2 becomes the least recently used
+        assert!(c.get(DestKey(1), now).is_some());
+        c.update(DestKey(3), measured(3.), now);
+        assert_eq!(c.len(), 2);
+        assert!(c.get(DestKey(2), now).is_none());
+        assert_eq!(c.get(DestKey(1), now).unwrap().bandwidth, 1.);
+        assert_eq!(c.get(DestKey(3), now).unwrap().bandwidth, 3.);
+    }
+
+    #[test]
+    fn test_staleness() {
+        let now = Instant::now();
+        let c = cache(2, UpdatePolicy::Always);
+        c.update(DestKey(1), measured(1.), now);
+        assert!(c.get(DestKey(1), now + Duration::from_secs(10)).is_some());
+        assert!(c.get(DestKey(1), now + Duration::from_secs(11)).is_none());
+        assert!(c.is_empty());
+    }
+
+    #[test]
+    fn test_update_policy() {
+        let now = Instant::now();
+        let c = cache(2, UpdatePolicy::MeasuredOnly);
+        let app_limited = EstimateUpdate {
+            is_app_limited: true,
+            ..measured(1.)
+        };
+        let not_converged = EstimateUpdate {
+            converged: false,
+            ..measured(1.)
+        };
+
+        // Unknown destinations take whatever is offered
+        assert!(c.update(DestKey(1), app_limited, now));
+        assert!(c.update(DestKey(1), measured(2.), now));
+        assert!(!c.update(DestKey(1), app_limited, now));
+        assert!(!c.update(DestKey(1), not_converged, now));
+        assert_eq!(c.get(DestKey(1), now).unwrap().bandwidth, 2.);
+
+        // Stale entries are replaced regardless
+        let later = now + Duration::from_secs(11);
+        assert!(c.update(DestKey(1), app_limited, later));
+        assert_eq!(c.get(DestKey(1), later).unwrap().bandwidth, 1.);
+    }
+}
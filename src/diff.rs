@@ -0,0 +1,255 @@
+use std::time::{Duration, Instant};
+
+use crate::{stats::ConnectionStats, ConnectionState, RateSample, SampleRejection};
+
+/// The fields in which two [`ConnectionState`]s diverged
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateDiff {
+    /// The earliest timestamp of both states; time fields are reported as offsets from it
+    pub epoch: Instant,
+    pub fields: Vec<FieldDiff>,
+}
+impl StateDiff {
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
+
+/// The latest two samples, as returned by [`ConnectionState::last_two_samples()`]
+pub type SamplePair = (Option<RateSample>, Option<RateSample>);
+
+/// A diverged field, as `(self, other)`
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldDiff {
+    Delivered(u64, u64),
+    DeliveredSource(u64, u64),
+    DeliveredAdjusted(u64, u64),
+    FecRecovered(u64, u64),
+    /// Offsets from [`StateDiff::epoch`]
+    DeliveredTime(Duration, Duration),
+    /// Offsets from [`StateDiff::epoch`]
+    FirstSentTime(Duration, Duration),
+    AppLimited(Option<u64>, Option<u64>),
+    IdleEpoch(u64, u64),
+    RoundCount(u64, u64),
+    NextRoundDelivered(u64, u64),
+    RoundStartDelivered(u64, u64),
+    RoundStartDeliveredSource(u64, u64),
+    RoundStartDeliveredAdjusted(u64, u64),
+    /// Offsets from [`StateDiff::epoch`]
+    RoundStartTime(Duration, Duration),
+    RoundAppLimited(bool, bool),
+    RoundRetransmit(bool, bool),
+    StartupDone(bool, bool),
+    InRecovery(bool, bool),
+    PostRecovery(bool, bool),
+    PostMigrationUntilRound(Option<u64>, Option<u64>),
+    /// Offsets from [`StateDiff::epoch`]
+    LastAckTime(Option<Duration>, Option<Duration>),
+    /// Offsets from [`StateDiff::epoch`]
+    AppLimitedSince(Option<Duration>, Option<Duration>),
+    MinRtt(Option<Duration>, Option<Duration>),
+    LatestRtt(Option<Duration>, Option<Duration>),
+    Stats(ConnectionStats, ConnectionStats),
+    EpochDelivered(Vec<u64>, Vec<u64>),
+    Context(u64, u64),
+    DeliveredAtLastSample(u64, u64),
+    LastRejection(Option<SampleRejection>, Option<SampleRejection>),
+    LastSamples(Box<SamplePair>, Box<SamplePair>),
+}
+
+impl ConnectionState {
+    /// Compare two estimator states, e.g. of two implementation variants fed the same events
+    ///
+    /// Every field is compared but the config, which is not comparable, the per-`ACK` scratch space, and the transmission numbering, which only tells packets apart.
+    pub fn diff(&self, other: &Self) -> StateDiff {
+        // Adding a field fails to compile here until it is compared
+        let Self {
+            delivered,
+            delivered_source,
+            delivered_adjusted,
+            fec_recovered,
+            delivered_time,
+            first_sent_time,
+            app_limited,
+            idle_epoch,
+            round_count,
+            next_round_delivered,
+            round_start_delivered,
+            round_start_delivered_source,
+            round_start_delivered_adjusted,
+            round_start_time,
+            round_app_limited,
+            round_retransmit,
+            startup_done,
+            in_recovery,
+            post_recovery,
+            post_migration_until_round,
+            last_ack_time,
+            app_limited_since,
+            min_rtt,
+            latest_rtt,
+            stats,
+            epoch_delivered,
+            context,
+            delivered_at_last_sample,
+            last_rejection,
+            last_samples,
+            transmissions: _,
+            acked_ids: _,
+            config: _,
+        } = self;
+
+        let instants = |c: &Self| {
+            [
+                Some(c.delivered_time),
+                Some(c.first_sent_time),
+                Some(c.round_start_time),
+                c.last_ack_time,
+                c.app_limited_since,
+            ]
+        };
+        let epoch = instants(self)
+            .into_iter()
+            .chain(instants(other))
+            .flatten()
+            .min()
+            .unwrap();
+        let offset = |t: Instant| t - epoch;
+
+        let mut fields = vec![];
+        macro_rules! compare {
+            ($($field:ident => $variant:ident),* $(,)?) => {
+                $(
+                    if *$field != other.$field {
+                        fields.push(FieldDiff::$variant($field.clone(), other.$field.clone()));
+                    }
+                )*
+            };
+        }
+        macro_rules! compare_time {
+            ($($field:ident => $variant:ident),* $(,)?) => {
+                $(
+                    if *$field != other.$field {
+                        fields.push(FieldDiff::$variant(offset(*$field), offset(other.$field)));
+                    }
+                )*
+            };
+        }
+        macro_rules! compare_time_opt {
+            ($($field:ident => $variant:ident),* $(,)?) => {
+                $(
+                    if *$field != other.$field {
+                        fields.push(FieldDiff::$variant(
+                            $field.map(offset),
+                            other.$field.map(offset),
+                        ));
+                    }
+                )*
+            };
+        }
+        compare!(
+            delivered => Delivered,
+            delivered_source => DeliveredSource,
+            delivered_adjusted => DeliveredAdjusted,
+            fec_recovered => FecRecovered,
+        );
+        compare_time!(
+            delivered_time => DeliveredTime,
+            first_sent_time => FirstSentTime,
+        );
+        compare!(
+            app_limited => AppLimited,
+            idle_epoch => IdleEpoch,
+            round_count => RoundCount,
+            next_round_delivered => NextRoundDelivered,
+            round_start_delivered => RoundStartDelivered,
+            round_start_delivered_source => RoundStartDeliveredSource,
+            round_start_delivered_adjusted => RoundStartDeliveredAdjusted,
+        );
+        compare_time!(round_start_time => RoundStartTime);
+        compare!(
+            round_app_limited => RoundAppLimited,
+            round_retransmit => RoundRetransmit,
+            startup_done => StartupDone,
+            in_recovery => InRecovery,
+            post_recovery => PostRecovery,
+            post_migration_until_round => PostMigrationUntilRound,
+        );
+        compare_time_opt!(
+            last_ack_time => LastAckTime,
+            app_limited_since => AppLimitedSince,
+        );
+        compare!(
+            min_rtt => MinRtt,
+            latest_rtt => LatestRtt,
+            stats => Stats,
+            epoch_delivered => EpochDelivered,
+            context => Context,
+            delivered_at_last_sample => DeliveredAtLastSample,
+            last_rejection => LastRejection,
+        );
+        if *last_samples != other.last_samples {
+            fields.push(FieldDiff::LastSamples(
+                Box::new(last_samples.clone()),
+                Box::new(other.last_samples.clone()),
+            ));
+        }
+        StateDiff { epoch, fields }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Packet;
+
+    #[test]
+    fn test_diff() {
+        let now = Instant::now();
+        let mut a = ConnectionState::new(now);
+        let p = a.send_packet_2(now, true);
+        let mut b = a.clone();
+        assert!(a.diff(&b).is_empty());
+
+        let s = Duration::from_secs(1);
+        a.sample_rate(
+            &[Packet {
+                state: p,
+                data_length: 1,
+            }],
+            now + s,
+            Duration::ZERO,
+        );
+        b.sample_rate(
+            &[Packet {
+                state: p,
+                data_length: 2,
+            }],
+            now + s,
+            Duration::ZERO,
+        );
+        let diff = a.diff(&b);
+        assert_eq!(diff.epoch, now);
+        assert!(diff.fields.contains(&FieldDiff::Delivered(1, 2)));
+        assert!(diff
+            .fields
+            .contains(&FieldDiff::EpochDelivered(vec![1], vec![2])));
+
+        let mut c = a.clone();
+        c.set_in_recovery(true);
+        c.set_application_limited_phases(0);
+        assert_eq!(
+            a.diff(&c).fields,
+            [
+                FieldDiff::AppLimited(None, Some(1)),
+                FieldDiff::InRecovery(false, true)
+            ]
+        );
+        c.on_rtt_sample(s / 2);
+        assert!(a
+            .diff(&c)
+            .fields
+            .contains(&FieldDiff::MinRtt(None, Some(s / 2))));
+    }
+}
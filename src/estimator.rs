@@ -0,0 +1,756 @@
+use std::{
+    collections::VecDeque,
+    fmt::Debug,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    destination_cache::CachedEstimate,
+    history::{SampleRing, SampleStorage},
+    DeliveryRate, RateKind, RateSample,
+};
+
+/// What [`WindowedMaxBandwidth`] and [`KalmanBandwidthFilter`] need from a sample
+///
+/// Implement it to push another bandwidth signal (e.g. receiver reports) through the same filters as [`RateSample`]s.
+pub trait RateSampleLike {
+    fn rate(&self) -> DeliveryRate;
+    /// Whether [`Self::rate()`] only bounds the capacity from below, as [`RateKind::LowerBound`]
+    fn is_lower_bound(&self) -> bool;
+    /// The time [`Self::rate()`] was measured over
+    fn interval(&self) -> Duration;
+    /// The round trip the sample belongs to, non-decreasing across samples
+    fn round(&self) -> u64;
+    /// Unreliable samples are ignored by the filters
+    fn is_reliable(&self) -> bool {
+        true
+    }
+}
+impl RateSampleLike for RateSample {
+    fn rate(&self) -> DeliveryRate {
+        self.delivery_rate()
+    }
+
+    fn is_lower_bound(&self) -> bool {
+        self.rate_kind() == RateKind::LowerBound
+    }
+
+    fn interval(&self) -> Duration {
+        RateSample::interval(self)
+    }
+
+    fn round(&self) -> u64 {
+        RateSample::round(self)
+    }
+
+    fn is_reliable(&self) -> bool {
+        RateSample::is_reliable(self)
+    }
+}
+
+/// A bandwidth estimate built from rate samples
+///
+/// Controllers hold a `Box<dyn BandwidthEstimator>` so that estimation approaches can be swapped and compared.
+pub trait BandwidthEstimator: Debug {
+    fn on_sample(&mut self, sample: &RateSample);
+    /// [`None`] until enough samples arrived
+    fn bandwidth(&self) -> Option<DeliveryRate>;
+    /// Forget every sample, e.g. after a path change
+    fn reset(&mut self);
+    /// Start from a cached bandwidth, e.g. from a [`DestinationCache`](crate::destination_cache::DestinationCache), instead of from nothing
+    ///
+    /// The prior is provisional: [`Self::bandwidth()`] reports it until the first measured sample (reliable and not [`RateKind::LowerBound`]),
+    /// which replaces it even if lower, so that a path slower than cached is not overestimated for long.
+    /// Ignored by default.
+    fn warm_start(&mut self, prior: &CachedEstimate) {
+        let _ = prior;
+    }
+    /// Whether [`Self::bandwidth()`] still rests on the [`Self::warm_start()`] prior rather than on measured samples
+    fn is_provisional(&self) -> bool {
+        false
+    }
+}
+
+/// The BBR-style max of the delivery rate samples over the last few round trips
+///
+/// - Rounds are taken from [`RateSampleLike::round()`].
+/// - Unreliable samples are ignored.
+/// - [`RateKind::LowerBound`] samples may only raise the estimate.
+/// - A [`BandwidthEstimator::warm_start()`] prior stands until the first measured sample or until it leaves the window.
+///
+/// Only the max of each round is kept, so at most `window` entries are stored:
+/// backed by a [`VecDeque`] by default, or by a fixed-capacity [`SampleRing`] via [`FixedWindowedMaxBandwidth`].
+#[derive(Debug, Clone)]
+pub struct WindowedMaxBandwidth<S = VecDeque<(u64, DeliveryRate)>> {
+    /// Measured in round trips
+    window: u64,
+    /// The latest round seen
+    round: u64,
+    /// (round, max delivery rate of the round), oldest first
+    samples: S,
+    /// (round, [`BandwidthEstimator::warm_start()`] prior), expiring like a sample
+    prior: Option<(u64, DeliveryRate)>,
+}
+/// A [`WindowedMaxBandwidth`] over a window of `N` rounds without heap allocation
+pub type FixedWindowedMaxBandwidth<const N: usize> =
+    WindowedMaxBandwidth<SampleRing<N, (u64, DeliveryRate)>>;
+
+impl WindowedMaxBandwidth {
+    /// `window`: measured in round trips, e.g. `10` in BBR
+    pub fn new(window: u64) -> Self {
+        Self {
+            window,
+            round: 0,
+            samples: VecDeque::new(),
+            prior: None,
+        }
+    }
+}
+impl<const N: usize> WindowedMaxBandwidth<SampleRing<N, (u64, DeliveryRate)>> {
+    /// The window is `N` round trips
+    pub fn fixed() -> Self {
+        Self {
+            window: N as u64,
+            round: 0,
+            samples: SampleRing::default(),
+            prior: None,
+        }
+    }
+}
+impl<S: SampleStorage<(u64, DeliveryRate)>> WindowedMaxBandwidth<S> {
+    /// [`BandwidthEstimator::on_sample()`] for any sample type
+    pub fn on_sample_like<T: RateSampleLike + ?Sized>(&mut self, sample: &T) {
+        self.round = self.round.max(sample.round());
+        while self
+            .samples
+            .front()
+            .is_some_and(|(round, _)| round + self.window <= self.round)
+        {
+            self.samples.pop_front();
+        }
+        if self
+            .prior
+            .is_some_and(|(round, _)| round + self.window <= self.round)
+        {
+            self.prior = None;
+        }
+        if !sample.is_reliable() {
+            return;
+        }
+        // A measured sample replaces the prior even if lower
+        if !sample.is_lower_bound() {
+            self.prior = None;
+        }
+        let rate = sample.rate();
+        if sample.is_lower_bound() && self.max_rate() >= Some(rate) {
+            return;
+        }
+        match self.samples.back_mut() {
+            Some((round, max)) if *round == self.round => {
+                if *max < rate {
+                    *max = rate;
+                }
+            }
+            _ => self.samples.push_back((self.round, rate)),
+        }
+    }
+
+    fn max_rate(&self) -> Option<DeliveryRate> {
+        self.samples
+            .iter()
+            .chain(&self.prior)
+            .map(|(_, rate)| *rate)
+            .reduce(|a, b| if a < b { b } else { a })
+    }
+}
+impl<S: SampleStorage<(u64, DeliveryRate)> + Debug> BandwidthEstimator for WindowedMaxBandwidth<S> {
+    fn on_sample(&mut self, sample: &RateSample) {
+        self.on_sample_like(sample);
+    }
+
+    fn bandwidth(&self) -> Option<DeliveryRate> {
+        self.max_rate()
+    }
+
+    fn reset(&mut self) {
+        while self.samples.pop_front().is_some() {}
+        self.prior = None;
+    }
+
+    fn warm_start(&mut self, prior: &CachedEstimate) {
+        self.prior = Some((
+            self.round,
+            DeliveryRate::from_bytes_per_sec(prior.bandwidth),
+        ));
+    }
+
+    fn is_provisional(&self) -> bool {
+        self.prior.is_some()
+    }
+}
+
+/// TCP Westwood's estimate: the data acknowledged per `ACK` interval, low-pass filtered
+///
+/// Each `ACK` yields `acked / ack_interarrival`; the mean of the last two is blended into the estimate with weight `1 - alpha`.
+///
+/// Unlike [`WindowedMaxBandwidth`], which takes the longer of the send and `ACK` intervals, this only looks at `ACK` arrivals.
+/// `ACK` compression (bunched `ACK`s followed by a gap) therefore inflates the estimate, since the short interarrivals dominate the average of the per-`ACK` rates.
+#[derive(Debug, Clone)]
+pub struct WestwoodBandwidth {
+    alpha: f64,
+    /// (connection delivered, `ACK` time) of the previous sample
+    last_ack: Option<(u64, Instant)>,
+    last_rate: Option<f64>,
+    estimate: Option<f64>,
+    /// [`Self::estimate`] is the [`BandwidthEstimator::warm_start()`] prior
+    is_provisional: bool,
+}
+impl WestwoodBandwidth {
+    /// The filter gain of the original Westwood
+    pub const ALPHA: f64 = 19. / 21.;
+
+    /// `alpha`: the weight of the previous estimate, in `[0, 1)`
+    pub fn new(alpha: f64) -> Self {
+        Self {
+            alpha,
+            last_ack: None,
+            last_rate: None,
+            estimate: None,
+            is_provisional: false,
+        }
+    }
+}
+impl Default for WestwoodBandwidth {
+    fn default() -> Self {
+        Self::new(Self::ALPHA)
+    }
+}
+impl BandwidthEstimator for WestwoodBandwidth {
+    fn on_sample(&mut self, sample: &RateSample) {
+        let delivered = sample.prior_delivered() + sample.delivered();
+        let ack_time = sample.prior_time() + sample.ack_elapsed();
+        let Some((last_delivered, last_time)) = self.last_ack.replace((delivered, ack_time)) else {
+            return;
+        };
+        let interarrival = ack_time.saturating_duration_since(last_time);
+        if interarrival.is_zero() {
+            // Fold into the next `ACK`
+            self.last_ack = Some((last_delivered, last_time));
+            return;
+        }
+        let rate = delivered.saturating_sub(last_delivered) as f64 / interarrival.as_secs_f64();
+        let filtered = (rate + self.last_rate.unwrap_or(rate)) / 2.;
+        self.last_rate = Some(rate);
+        let estimate = self.estimate.filter(|_| !self.is_provisional);
+        self.estimate = Some(estimate.map_or(filtered, |estimate| {
+            self.alpha * estimate + (1. - self.alpha) * filtered
+        }));
+        self.is_provisional = false;
+    }
+
+    fn bandwidth(&self) -> Option<DeliveryRate> {
+        self.estimate.map(DeliveryRate::from_bytes_per_sec)
+    }
+
+    fn reset(&mut self) {
+        self.last_ack = None;
+        self.last_rate = None;
+        self.estimate = None;
+        self.is_provisional = false;
+    }
+
+    fn warm_start(&mut self, prior: &CachedEstimate) {
+        self.estimate = Some(prior.bandwidth);
+        self.is_provisional = true;
+    }
+
+    fn is_provisional(&self) -> bool {
+        self.is_provisional
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct KalmanConfig {
+    /// How fast the bandwidth is expected to drift, as a standard deviation relative to the estimate, per sample
+    ///
+    /// Larger values track changes faster but smooth less.
+    pub process_noise: f64,
+    /// The noise of a [`RateKind::Measured`] sample, as a standard deviation relative to the sampled rate
+    pub measurement_noise: f64,
+    /// [`RateKind::LowerBound`] samples have their measurement variance multiplied by this
+    pub lower_bound_noise_scale: f64,
+    /// A sample further than this many standard deviations of the innovation from the estimate is rejected as an outlier
+    pub gate: f64,
+    /// After this many consecutive rejections the bandwidth is taken to have shifted, and the filter restarts from the latest sample
+    pub max_rejections: u32,
+}
+impl Default for KalmanConfig {
+    fn default() -> Self {
+        Self {
+            process_noise: 0.02,
+            measurement_noise: 0.1,
+            lower_bound_noise_scale: 4.,
+            gate: 3.,
+            max_rejections: 3,
+        }
+    }
+}
+
+/// A one-dimensional Kalman filter tracking the bandwidth as a slowly varying state
+///
+/// Each sample's delivery rate is a measurement whose variance reflects the confidence in it:
+/// - Unreliable samples are ignored.
+/// - [`RateKind::LowerBound`] samples below the estimate are ignored, and the rest count with inflated variance.
+/// - Outliers are gated out (see [`KalmanConfig::gate`]).
+///
+/// Unlike [`WindowedMaxBandwidth`], the estimate is not biased high by single outliers.
+#[derive(Debug, Clone)]
+pub struct KalmanBandwidthFilter {
+    config: KalmanConfig,
+    /// (estimate, variance)
+    state: Option<(f64, f64)>,
+    rejections: u32,
+    /// [`Self::state`] is the [`BandwidthEstimator::warm_start()`] prior
+    is_provisional: bool,
+}
+impl KalmanBandwidthFilter {
+    pub fn new(config: KalmanConfig) -> Self {
+        Self {
+            config,
+            state: None,
+            rejections: 0,
+            is_provisional: false,
+        }
+    }
+
+    /// [`None`] until a sample is taken
+    pub fn estimate(&self) -> Option<DeliveryRate> {
+        self.state
+            .map(|(estimate, _)| DeliveryRate::from_bytes_per_sec(estimate))
+    }
+
+    /// The variance of [`Self::estimate()`], measured in the square of its unit
+    pub fn variance(&self) -> Option<f64> {
+        self.state.map(|(_, variance)| variance)
+    }
+
+    /// [`BandwidthEstimator::on_sample()`] for any sample type
+    pub fn on_sample_like<S: RateSampleLike + ?Sized>(&mut self, sample: &S) {
+        if !sample.is_reliable() {
+            return;
+        }
+        let z = sample.rate().as_bytes_per_sec();
+        let is_lower_bound = sample.is_lower_bound();
+        let mut r = (self.config.measurement_noise * z).powi(2);
+        if is_lower_bound {
+            r *= self.config.lower_bound_noise_scale;
+        }
+        // A zero rate would have no noise, and a zero-variance estimate would make the gain `0 / 0`
+        let r = r.max(f64::MIN_POSITIVE);
+        if self.is_provisional && !is_lower_bound {
+            self.is_provisional = false;
+            self.rejections = 0;
+            self.state = Some((z, r));
+            return;
+        }
+        let Some((x, p)) = self.state else {
+            if !is_lower_bound {
+                self.state = Some((z, r));
+            }
+            return;
+        };
+        if is_lower_bound && z <= x {
+            return;
+        }
+        // Predict
+        let p = p + (self.config.process_noise * x).powi(2);
+        // Update
+        let innovation = z - x;
+        let s = p + r;
+        if self.config.gate.powi(2) * s < innovation.powi(2) {
+            self.rejections += 1;
+            self.state = Some(if self.config.max_rejections <= self.rejections {
+                self.rejections = 0;
+                (z, r)
+            } else {
+                (x, p)
+            });
+            return;
+        }
+        self.rejections = 0;
+        let k = p / s;
+        self.state = Some((x + k * innovation, (1. - k) * p));
+    }
+}
+impl BandwidthEstimator for KalmanBandwidthFilter {
+    fn on_sample(&mut self, sample: &RateSample) {
+        self.on_sample_like(sample);
+    }
+
+    fn bandwidth(&self) -> Option<DeliveryRate> {
+        self.estimate()
+    }
+
+    fn reset(&mut self) {
+        self.state = None;
+        self.rejections = 0;
+        self.is_provisional = false;
+    }
+
+    fn warm_start(&mut self, prior: &CachedEstimate) {
+        let variance = (self.config.measurement_noise * prior.bandwidth).powi(2);
+        self.state = Some((prior.bandwidth, variance.max(f64::MIN_POSITIVE)));
+        self.rejections = 0;
+        self.is_provisional = true;
+    }
+
+    fn is_provisional(&self) -> bool {
+        self.is_provisional
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{test_util::RateSampleBuilder, ConnectionState, Packet, PacketState};
+
+    /// 1_000 octets sent every 10 ms, each acknowledged 50 ms later plus `ack_delay(k)`
+    fn run(estimators: &mut [Box<dyn BandwidthEstimator>], ack_delay: impl Fn(u32) -> Duration) {
+        let start = Instant::now();
+        let ms = Duration::from_millis(1);
+        let mut c = ConnectionState::new(start);
+        let mut events = vec![];
+        for k in 0..500 {
+            let sent = start + k * 10 * ms;
+            events.push((sent + 50 * ms + ack_delay(k), k));
+        }
+        let mut sent: Vec<PacketState> = vec![];
+        let mut acks = events.into_iter().peekable();
+        for k in 0..500 {
+            let now = start + k * 10 * ms;
+            while let Some((t, i)) = acks.next_if(|(t, _)| *t <= now) {
+                let packet = Packet {
+                    state: sent[i as usize],
+                    data_length: 1_000,
+                };
+                if let Some(rs) = c.sample_rate(&[packet], t, Duration::ZERO) {
+                    estimators.iter_mut().for_each(|e| e.on_sample(&rs));
+                }
+            }
+            sent.push(c.send_packet_2(now, k == 0));
+        }
+    }
+
+    fn estimators() -> Vec<Box<dyn BandwidthEstimator>> {
+        vec![
+            Box::new(WindowedMaxBandwidth::new(10)),
+            Box::new(WestwoodBandwidth::default()),
+            Box::new(FixedWindowedMaxBandwidth::<10>::fixed()),
+        ]
+    }
+
+    #[test]
+    fn test_clean_link_converges() {
+        let mut estimators = estimators();
+        run(&mut estimators, |_| Duration::ZERO);
+        for e in &estimators {
+            let bw = e.bandwidth().unwrap().as_bytes_per_sec();
+            assert!((bw - 100_000.).abs() < 1_000., "{e:?}");
+        }
+    }
+
+    #[test]
+    fn test_ack_compression() {
+        // `ACK`s bunched into groups of five, 1 ms apart
+        let mut estimators = estimators();
+        let ms = Duration::from_millis(1);
+        run(&mut estimators, |k| {
+            let pos = k % 5;
+            (4 - pos) * 10 * ms - (4 - pos) * ms
+        });
+        let windowed_max = estimators[0].bandwidth().unwrap().as_bytes_per_sec();
+        let westwood = estimators[1].bandwidth().unwrap().as_bytes_per_sec();
+        assert!(windowed_max < 150_000., "{windowed_max}");
+        assert!(3. * windowed_max < westwood, "{westwood}");
+        // Both storages behave the same
+        assert_eq!(estimators[2].bandwidth(), estimators[0].bandwidth());
+
+        estimators[1].reset();
+        assert_eq!(estimators[1].bandwidth(), None);
+    }
+
+    /// A sender pacing at twice the estimate (or ten packets per round trip before one) over a bottleneck of `link` octets per second and a 50 ms RTT
+    ///
+    /// Returns the estimate at the end of each round trip, and the estimate after each sample.
+    fn paced_transfer(
+        e: &mut dyn BandwidthEstimator,
+        link: f64,
+        rounds: u32,
+    ) -> (Vec<f64>, Vec<f64>) {
+        let start = Instant::now();
+        let rtt = Duration::from_millis(50);
+        let mss = 1_000.;
+        let serialization = Duration::from_secs_f64(mss / link);
+        let mut c = ConnectionState::new(start);
+        let mut acks = VecDeque::new();
+        let mut link_free = start;
+        let mut next_send = start;
+        let mut estimates = vec![];
+        let mut per_sample = vec![];
+        while estimates.len() < rounds as usize {
+            let next_round = start + rtt * (estimates.len() as u32 + 1);
+            if acks
+                .front()
+                .is_some_and(|(t, _)| *t <= next_send.min(next_round))
+            {
+                let (t, state) = acks.pop_front().unwrap();
+                let packet = Packet {
+                    state,
+                    data_length: mss as u64,
+                };
+                if let Some(rs) = c.sample_rate(&[packet], t, Duration::ZERO) {
+                    e.on_sample(&rs);
+                    per_sample.push(e.bandwidth().map_or(0., |bw| bw.as_bytes_per_sec()));
+                }
+            } else if next_send < next_round {
+                let state = c.send_packet_2(next_send, next_send == start);
+                link_free = link_free.max(next_send) + serialization;
+                acks.push_back((link_free + rtt, state));
+                let rate = e.bandwidth().map_or(10. * mss / rtt.as_secs_f64(), |bw| {
+                    2. * bw.as_bytes_per_sec()
+                });
+                next_send += Duration::from_secs_f64(mss / rate);
+            } else {
+                estimates.push(e.bandwidth().map_or(0., |bw| bw.as_bytes_per_sec()));
+            }
+        }
+        (estimates, per_sample)
+    }
+
+    fn all_estimators() -> Vec<Box<dyn BandwidthEstimator>> {
+        let mut estimators = estimators();
+        estimators.push(Box::new(
+            KalmanBandwidthFilter::new(KalmanConfig::default()),
+        ));
+        estimators
+    }
+
+    fn cached(bandwidth: f64) -> CachedEstimate {
+        CachedEstimate {
+            bandwidth,
+            min_rtt: Duration::from_millis(50),
+            updated_at: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn test_warm_start_reaches_full_rate_sooner() {
+        let link = 10_000_000.;
+        let rounds_to_full =
+            |estimates: Vec<f64>| estimates.iter().position(|bw| 0.9 * link <= *bw).unwrap();
+        for (mut cold, mut warm) in all_estimators().into_iter().zip(all_estimators()) {
+            warm.warm_start(&cached(link));
+            assert!(warm.is_provisional());
+            let cold = rounds_to_full(paced_transfer(cold.as_mut(), link, 20).0);
+            let (estimates, _) = paced_transfer(warm.as_mut(), link, 20);
+            assert!(!warm.is_provisional(), "{warm:?}");
+            assert!(estimates.iter().all(|bw| *bw < 1.1 * link), "{warm:?}");
+            let warm = rounds_to_full(estimates);
+            assert!(warm + 3 <= cold, "{warm} {cold}");
+        }
+    }
+
+    #[test]
+    fn test_warm_start_slower_path() {
+        // The path is a quarter as fast as cached
+        let link = 2_500_000.;
+        for mut e in all_estimators() {
+            e.warm_start(&cached(4. * link));
+            let (_, per_sample) = paced_transfer(e.as_mut(), link, 20);
+            // The first rate overrides the prior; Westwood takes two `ACK`s for one
+            assert!(!e.is_provisional(), "{e:?}");
+            assert!(
+                per_sample.iter().skip(1).all(|bw| *bw < 1.1 * link),
+                "{e:?} {per_sample:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_warm_start_provisional() {
+        let mut windowed_max = WindowedMaxBandwidth::new(10);
+        windowed_max.warm_start(&cached(200_000.));
+        // A lower bound under the prior says nothing
+        let app_limited = RateSampleBuilder::new(Instant::now())
+            .delivery_rate(50_000.)
+            .is_app_limited(true)
+            .build();
+        windowed_max.on_sample(&app_limited);
+        assert!(windowed_max.is_provisional());
+        assert_eq!(
+            windowed_max.bandwidth().unwrap().as_bytes_per_sec(),
+            200_000.
+        );
+        // A single measured sample under the prior replaces it
+        windowed_max.on_sample(&measured(100_000.));
+        assert!(!windowed_max.is_provisional());
+        assert_eq!(
+            windowed_max.bandwidth().unwrap().as_bytes_per_sec(),
+            100_000.
+        );
+
+        // An unused prior leaves the window like a sample
+        let mut windowed_max = WindowedMaxBandwidth::new(3);
+        windowed_max.warm_start(&cached(200_000.));
+        let app_limited = |round| {
+            RateSampleBuilder::new(Instant::now())
+                .delivery_rate(50_000.)
+                .is_app_limited(true)
+                .round(round)
+                .build()
+        };
+        windowed_max.on_sample(&app_limited(2));
+        assert!(windowed_max.is_provisional());
+        windowed_max.on_sample(&app_limited(3));
+        assert!(!windowed_max.is_provisional());
+        assert_eq!(
+            windowed_max.bandwidth().unwrap().as_bytes_per_sec(),
+            50_000.
+        );
+
+        windowed_max.warm_start(&cached(200_000.));
+        windowed_max.reset();
+        assert!(!windowed_max.is_provisional());
+        assert_eq!(windowed_max.bandwidth(), None);
+    }
+
+    fn measured(rate: f64) -> RateSample {
+        RateSampleBuilder::new(Instant::now())
+            .delivery_rate(rate)
+            .build()
+    }
+
+    #[test]
+    fn test_kalman_converges() {
+        let mut kalman = KalmanBandwidthFilter::new(KalmanConfig::default());
+        assert_eq!(kalman.estimate(), None);
+        // ±10% around 100_000
+        for k in 0..200 {
+            let noise = [0., 10_000., -10_000., 5_000., -5_000.][k % 5];
+            kalman.on_sample(&measured(100_000. + noise));
+        }
+        let estimate = kalman.estimate().unwrap().as_bytes_per_sec();
+        assert!((estimate - 100_000.).abs() < 2_000., "{estimate}");
+        let std_dev = kalman.variance().unwrap().sqrt();
+        assert!(std_dev < 10_000., "{std_dev}");
+
+        // Follow a shift
+        for _ in 0..50 {
+            kalman.on_sample(&measured(50_000.));
+        }
+        let estimate = kalman.estimate().unwrap().as_bytes_per_sec();
+        assert!((estimate - 50_000.).abs() < 2_000., "{estimate}");
+    }
+
+    #[test]
+    fn test_kalman_outlier_rejected() {
+        let mut kalman = KalmanBandwidthFilter::new(KalmanConfig::default());
+        for _ in 0..50 {
+            kalman.on_sample(&measured(100_000.));
+        }
+        kalman.on_sample(&measured(1_000_000.));
+        assert_eq!(kalman.estimate().unwrap().as_bytes_per_sec(), 100_000.);
+        // A lower bound under the estimate says nothing
+        let app_limited = RateSampleBuilder::new(Instant::now())
+            .delivery_rate(10_000.)
+            .is_app_limited(true)
+            .build();
+        kalman.on_sample(&app_limited);
+        assert_eq!(kalman.estimate().unwrap().as_bytes_per_sec(), 100_000.);
+
+        let mut windowed_max = WindowedMaxBandwidth::new(10);
+        windowed_max.on_sample(&measured(100_000.));
+        windowed_max.on_sample(&measured(1_000_000.));
+        assert_eq!(
+            windowed_max.bandwidth().unwrap().as_bytes_per_sec(),
+            1_000_000.
+        );
+    }
+
+    #[test]
+    fn test_kalman_zero_rate() {
+        let mut kalman = KalmanBandwidthFilter::new(KalmanConfig::default());
+        kalman.on_sample(&measured(0.));
+        kalman.on_sample(&measured(0.));
+        assert_eq!(kalman.estimate().unwrap().as_bytes_per_sec(), 0.);
+        assert!(!kalman.variance().unwrap().is_nan());
+
+        // The link comes alive
+        for _ in 0..10 {
+            kalman.on_sample(&measured(100_000.));
+        }
+        let estimate = kalman.estimate().unwrap().as_bytes_per_sec();
+        assert!((estimate - 100_000.).abs() < 2_000., "{estimate}");
+    }
+
+    /// E.g. a rate reported back by the receiver once per round trip
+    struct ReceiverReport {
+        rate: f64,
+        round: u64,
+    }
+    impl RateSampleLike for ReceiverReport {
+        fn rate(&self) -> DeliveryRate {
+            DeliveryRate::from_bytes_per_sec(self.rate)
+        }
+
+        fn is_lower_bound(&self) -> bool {
+            false
+        }
+
+        fn interval(&self) -> Duration {
+            Duration::from_millis(50)
+        }
+
+        fn round(&self) -> u64 {
+            self.round
+        }
+    }
+
+    #[test]
+    fn test_custom_sample_type() {
+        custom_sample_type(WindowedMaxBandwidth::new(3));
+        custom_sample_type(FixedWindowedMaxBandwidth::<3>::fixed());
+    }
+
+    fn custom_sample_type<S: SampleStorage<(u64, DeliveryRate)> + Debug>(
+        mut windowed_max: WindowedMaxBandwidth<S>,
+    ) {
+        let mut kalman = KalmanBandwidthFilter::new(KalmanConfig::default());
+        for (round, rate) in [200_000., 100_000., 100_000., 100_000., 100_000.]
+            .into_iter()
+            .enumerate()
+        {
+            let report = ReceiverReport {
+                rate,
+                round: round as u64,
+            };
+            windowed_max.on_sample_like(&report);
+            kalman.on_sample_like(&report);
+        }
+        // The 200_000 expired out of the three-round window
+        assert_eq!(
+            windowed_max.bandwidth().unwrap().as_bytes_per_sec(),
+            100_000.
+        );
+        assert!(kalman.bandwidth().unwrap().as_bytes_per_sec() < 200_000.);
+
+        // Mixed with the crate's own samples
+        windowed_max.on_sample(&measured(150_000.));
+        assert_eq!(
+            windowed_max.bandwidth().unwrap().as_bytes_per_sec(),
+            150_000.
+        );
+    }
+}
@@ -0,0 +1,227 @@
+//! A simplified estimator exported to Swift and Kotlin through uniffi
+//!
+//! Timestamps are microseconds since an origin of the caller's choice, e.g. the platform's monotonic clock, as [`Instant`]s do not cross the boundary.
+//! Data is measured in octets.
+//!
+//! Build the library for the target platform and generate the bindings from it:
+//!
+//! ```sh
+//! cargo rustc --lib --release --features uniffi --crate-type cdylib
+//! cargo run --features uniffi-bindgen --bin uniffi-bindgen -- generate --library target/release/libdre.so --language kotlin --out-dir out
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    estimator::{BandwidthEstimator, WindowedMaxBandwidth},
+    ConnectionState, Packet, RateSample,
+};
+
+/// The bandwidth is the max over this many round trips
+const BW_WINDOW_ROUNDS: u64 = 10;
+
+/// Thrown to Swift and Kotlin instead of panicking
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Error)]
+pub enum EstimatorError {
+    /// The packet was never sent by [`Estimator::on_send()`] or was already acknowledged
+    UnknownPacket { packet_id: u64 },
+    /// The timestamp lies beyond what the platform clock represents
+    TimestampOutOfRange { micros: u64 },
+}
+impl std::fmt::Display for EstimatorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownPacket { packet_id } => {
+                write!(f, "packet {packet_id} is not in flight")
+            }
+            Self::TimestampOutOfRange { micros } => {
+                write!(f, "timestamp {micros} us is out of range")
+            }
+        }
+    }
+}
+impl std::error::Error for EstimatorError {}
+
+#[derive(Debug)]
+struct Inner {
+    state: ConnectionState,
+    bandwidth: WindowedMaxBandwidth,
+    /// Packet id -> packet in flight
+    sent: HashMap<u64, Packet>,
+    next_packet_id: u64,
+    bytes_in_flight: u64,
+    latest: Option<RateSample>,
+}
+
+/// Delivery rate sampling and the windowed max bandwidth of one connection
+#[derive(Debug, uniffi::Object)]
+pub struct Estimator {
+    /// The [`Instant`] the caller's timestamps count from
+    origin: Instant,
+    inner: Mutex<Inner>,
+}
+#[uniffi::export]
+impl Estimator {
+    /// `now_micros`: the caller's current timestamp, fixing the origin it counts from
+    #[uniffi::constructor]
+    pub fn new(now_micros: u64) -> Arc<Self> {
+        let now = Instant::now();
+        let origin = now
+            .checked_sub(Duration::from_micros(now_micros))
+            .unwrap_or(now);
+        let now = origin + Duration::from_micros(now_micros);
+        Arc::new(Self {
+            origin,
+            inner: Mutex::new(Inner {
+                state: ConnectionState::new(now),
+                bandwidth: WindowedMaxBandwidth::new(BW_WINDOW_ROUNDS),
+                sent: HashMap::new(),
+                next_packet_id: 0,
+                bytes_in_flight: 0,
+                latest: None,
+            }),
+        })
+    }
+
+    /// Upon sending a packet of `bytes`, returning its id to pass to [`Self::on_ack()`]
+    pub fn on_send(&self, bytes: u64, now_micros: u64) -> Result<u64, EstimatorError> {
+        let now = self.instant(now_micros)?;
+        let inner = &mut *self.lock();
+        let state = inner.state.send_packet_2(now, inner.bytes_in_flight == 0);
+        let packet_id = inner.next_packet_id;
+        inner.next_packet_id += 1;
+        inner.bytes_in_flight += bytes;
+        inner.sent.insert(
+            packet_id,
+            Packet {
+                state,
+                data_length: bytes,
+            },
+        );
+        Ok(packet_id)
+    }
+
+    /// Upon an `ACK` of the packets `packet_ids`
+    ///
+    /// An id listed twice counts once.
+    /// If any id is not in flight, nothing is acknowledged.
+    pub fn on_ack(
+        &self,
+        packet_ids: Vec<u64>,
+        now_micros: u64,
+        min_rtt_micros: u64,
+    ) -> Result<(), EstimatorError> {
+        let now = self.instant(now_micros)?;
+        let inner = &mut *self.lock();
+        let mut seen = HashSet::new();
+        let packet_ids: Vec<u64> = packet_ids
+            .into_iter()
+            .filter(|id| seen.insert(*id))
+            .collect();
+        if let Some(&packet_id) = packet_ids.iter().find(|id| !inner.sent.contains_key(id)) {
+            return Err(EstimatorError::UnknownPacket { packet_id });
+        }
+        let acked: Vec<Packet> = packet_ids
+            .iter()
+            .filter_map(|id| inner.sent.remove(id))
+            .collect();
+        let bytes: u64 = acked.iter().map(|p| p.data_length).sum();
+        inner.bytes_in_flight = inner.bytes_in_flight.saturating_sub(bytes);
+        let min_rtt = Duration::from_micros(min_rtt_micros);
+        let Some(sample) = inner.state.sample_rate(&acked, now, min_rtt) else {
+            return Ok(());
+        };
+        inner.bandwidth.on_sample(&sample);
+        inner.latest = Some(sample);
+        Ok(())
+    }
+
+    /// Mark the connection application-limited, e.g. when the measurement has no more data to send for a while
+    pub fn mark_app_limited(&self) {
+        let inner = &mut *self.lock();
+        let pipe = inner.bytes_in_flight;
+        inner.state.mark_app_limited(pipe);
+    }
+
+    /// The windowed max of the measured delivery rate, in octets per second
+    pub fn bandwidth_bps(&self) -> Option<f64> {
+        self.lock()
+            .bandwidth
+            .bandwidth()
+            .map(|bw| bw.as_bytes_per_sec())
+    }
+
+    /// Whether the most recent rate sample was application-limited, so that it only bounds the bandwidth from below
+    pub fn is_app_limited(&self) -> bool {
+        self.lock()
+            .latest
+            .as_ref()
+            .is_some_and(|sample| sample.is_app_limited())
+    }
+}
+impl Estimator {
+    fn instant(&self, micros: u64) -> Result<Instant, EstimatorError> {
+        self.origin
+            .checked_add(Duration::from_micros(micros))
+            .ok_or(EstimatorError::TimestampOutOfRange { micros })
+    }
+
+    /// A panic never leaves the state half-updated, so a poisoned lock is still usable
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exported_api() {
+        // 1_200 octets every millisecond behind a 50 ms RTT
+        let t0 = 1_000_000;
+        let e = Estimator::new(t0);
+        assert_eq!(e.bandwidth_bps(), None);
+        assert!(!e.is_app_limited());
+        let mut in_flight = std::collections::VecDeque::new();
+        for ms in 0..500 {
+            let now = t0 + ms * 1_000;
+            while let Some(&(sent, id)) = in_flight.front() {
+                if now < sent + 50_000 {
+                    break;
+                }
+                in_flight.pop_front();
+                e.on_ack(vec![id], now, 50_000).unwrap();
+            }
+            in_flight.push_back((now, e.on_send(1_200, now).unwrap()));
+        }
+        let bw = e.bandwidth_bps().unwrap();
+        assert!((bw - 1_200_000.).abs() < 50_000., "{bw}");
+        assert!(!e.is_app_limited());
+
+        // Errors are returned, and the state is untouched
+        let (_, id) = in_flight.pop_front().unwrap();
+        let now = t0 + 600_000;
+        assert_eq!(
+            e.on_ack(vec![id, u64::MAX], now, 50_000),
+            Err(EstimatorError::UnknownPacket {
+                packet_id: u64::MAX
+            })
+        );
+        e.on_ack(vec![id, id], now, 50_000).unwrap();
+        assert_eq!(
+            e.on_ack(vec![id], now, 50_000),
+            Err(EstimatorError::UnknownPacket { packet_id: id })
+        );
+
+        // Application-limited once the measurement stops filling the path
+        e.mark_app_limited();
+        let id = e.on_send(1_200, now).unwrap();
+        e.on_ack(vec![id], now + 50_000, 50_000).unwrap();
+        assert!(e.is_app_limited());
+    }
+}
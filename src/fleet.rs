@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+use crate::RateSample;
+
+/// Fleet-wide statistics of a [`FleetAggregator`]
+///
+/// The rates are measured in octets or packets per second.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FleetSummary {
+    pub connections: usize,
+    /// The sum of the latest rate of every connection
+    pub total_rate: f64,
+    /// The median of the latest per-connection rates, the mean of the two middle ones for an even count; [`None`] with no connection
+    pub median_rate: Option<f64>,
+    /// The connections whose latest sample is [`RateSample::is_app_limited()`]
+    pub app_limited: usize,
+}
+
+/// The latest [`RateSample`] of every connection of a server, summarized for metrics export
+///
+/// Connections are told apart by [`RateSample::context()`], so stamp each one with [`crate::ConnectionState::set_context()`].
+///
+/// Allocation-light: a connection takes a slot on its first sample and keeps it until [`Self::remove()`], and [`Self::summary()`] reuses a scratch buffer.
+#[derive(Debug, Clone, Default)]
+pub struct FleetAggregator {
+    /// Context to index into [`Self::latest`]
+    index: HashMap<u64, usize>,
+    /// (context, rate, app-limited)
+    latest: Vec<(u64, f64, bool)>,
+    scratch: Vec<f64>,
+}
+impl FleetAggregator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the connection's latest sample
+    ///
+    /// Unreliable samples are ignored.
+    pub fn ingest(&mut self, sample: &RateSample) {
+        if !sample.is_reliable() {
+            return;
+        }
+        let entry = (
+            sample.context(),
+            sample.delivery_rate_raw(),
+            sample.is_app_limited(),
+        );
+        match self.index.get(&sample.context()) {
+            Some(&i) => self.latest[i] = entry,
+            None => {
+                self.index.insert(sample.context(), self.latest.len());
+                self.latest.push(entry);
+            }
+        }
+    }
+
+    /// Forget a closed connection
+    pub fn remove(&mut self, context: u64) {
+        let Some(i) = self.index.remove(&context) else {
+            return;
+        };
+        self.latest.swap_remove(i);
+        if let Some((moved, _, _)) = self.latest.get(i) {
+            self.index.insert(*moved, i);
+        }
+    }
+
+    pub fn summary(&mut self) -> FleetSummary {
+        self.scratch.clear();
+        self.scratch
+            .extend(self.latest.iter().map(|(_, rate, _)| *rate));
+        let n = self.scratch.len();
+        let median_rate = (n != 0).then(|| {
+            let (_, upper, _) = self.scratch.select_nth_unstable_by(n / 2, f64::total_cmp);
+            let upper = *upper;
+            if n % 2 == 1 {
+                return upper;
+            }
+            let lower = self.scratch[..n / 2]
+                .iter()
+                .copied()
+                .max_by(f64::total_cmp)
+                .unwrap();
+            (lower + upper) / 2.
+        });
+        FleetSummary {
+            connections: n,
+            total_rate: self.scratch.iter().sum(),
+            median_rate,
+            app_limited: self
+                .latest
+                .iter()
+                .filter(|(_, _, limited)| *limited)
+                .count(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::test_util::RateSampleBuilder;
+
+    #[test]
+    fn test_synthetic_fleet() {
+        let now = Instant::now();
+        let mut fleet = FleetAggregator::new();
+        assert_eq!(fleet.summary(), FleetSummary::default());
+
+        // Connection `k` at `k * 1_000`, every tenth one app-limited
+        for k in 1..=1_000 {
+            let rs = RateSampleBuilder::new(now)
+                .context(k)
+                .delivery_rate(k as f64 * 1_000.)
+                .is_app_limited(k % 10 == 0)
+                .build();
+            fleet.ingest(&rs);
+        }
+        let summary = fleet.summary();
+        assert_eq!(summary.connections, 1_000);
+        assert_eq!(summary.total_rate, 500_500_000.);
+        assert_eq!(summary.median_rate, Some(500_500.));
+        assert_eq!(summary.app_limited, 100);
+
+        // Only the latest sample counts
+        let rs = RateSampleBuilder::new(now)
+            .context(1_000)
+            .delivery_rate(0.)
+            .build();
+        fleet.ingest(&rs);
+        let unreliable = RateSampleBuilder::new(now)
+            .context(1)
+            .delivery_rate(1e12)
+            .is_reliable(false)
+            .build();
+        fleet.ingest(&unreliable);
+        fleet.remove(500);
+        fleet.remove(500);
+        let summary = fleet.summary();
+        assert_eq!(summary.connections, 999);
+        assert_eq!(summary.total_rate, 500_500_000. - 1_000_000. - 500_000.);
+        assert_eq!(summary.median_rate, Some(499_000.));
+        assert_eq!(summary.app_limited, 98);
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::VecDeque;
+
+use crate::RateSample;
+
+/// What [`RateSampleHistory`] does with a sample pushed at capacity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Evict the oldest sample to make room
+    #[default]
+    DropOldest,
+    /// Discard the pushed sample
+    DropNewest,
+}
+
+/// Where [`RateSampleHistory`] and [`crate::estimator::WindowedMaxBandwidth`] keep their entries
+///
+/// Neither pushes beyond its capacity, so a storage only has to hold that many entries.
+pub trait SampleStorage<T = RateSample> {
+    fn push_back(&mut self, sample: T);
+    fn pop_front(&mut self) -> Option<T>;
+    fn back_mut(&mut self) -> Option<&mut T>;
+    /// Oldest first
+    fn iter<'a>(&'a self) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a;
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    fn front(&self) -> Option<&T> {
+        self.iter().next()
+    }
+}
+impl<T> SampleStorage<T> for VecDeque<T> {
+    fn push_back(&mut self, sample: T) {
+        VecDeque::push_back(self, sample);
+    }
+    fn pop_front(&mut self) -> Option<T> {
+        VecDeque::pop_front(self)
+    }
+    fn back_mut(&mut self) -> Option<&mut T> {
+        VecDeque::back_mut(self)
+    }
+    fn iter<'a>(&'a self) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a,
+    {
+        VecDeque::iter(self)
+    }
+    fn len(&self) -> usize {
+        VecDeque::len(self)
+    }
+}
+
+/// A ring buffer of at most `N` entries, allocated inline
+#[derive(Debug, Clone)]
+pub struct SampleRing<const N: usize, T = RateSample> {
+    slots: [Option<T>; N],
+    head: usize,
+    len: usize,
+}
+impl<const N: usize, T> Default for SampleRing<N, T> {
+    fn default() -> Self {
+        Self {
+            slots: std::array::from_fn(|_| None),
+            head: 0,
+            len: 0,
+        }
+    }
+}
+impl<const N: usize, T> SampleStorage<T> for SampleRing<N, T> {
+    /// # Panics
+    ///
+    /// If the ring already holds `N` entries
+    fn push_back(&mut self, sample: T) {
+        assert!(self.len < N, "sample ring is full");
+        self.slots[(self.head + self.len) % N] = Some(sample);
+        self.len += 1;
+    }
+    fn pop_front(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        let sample = self.slots[self.head].take();
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        sample
+    }
+    fn back_mut(&mut self) -> Option<&mut T> {
+        let last = self.len.checked_sub(1)?;
+        self.slots[(self.head + last) % N].as_mut()
+    }
+    fn iter<'a>(&'a self) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a,
+    {
+        (0..self.len).map(|i| self.slots[(self.head + i) % N].as_ref().unwrap())
+    }
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+/// The most recent rate samples, in memory bounded by a capacity
+///
+/// Backed by a [`VecDeque`] by default, or by a fixed-capacity [`SampleRing`] via [`FixedRateSampleHistory`].
+#[derive(Debug, Clone)]
+pub struct RateSampleHistory<S = VecDeque<RateSample>> {
+    samples: S,
+    capacity: usize,
+    policy: OverflowPolicy,
+    dropped: u64,
+}
+/// A [`RateSampleHistory`] holding at most `N` samples without heap allocation
+pub type FixedRateSampleHistory<const N: usize> = RateSampleHistory<SampleRing<N>>;
+
+impl RateSampleHistory {
+    pub fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+            policy,
+            dropped: 0,
+        }
+    }
+}
+impl<const N: usize> RateSampleHistory<SampleRing<N>> {
+    /// The capacity is `N`
+    pub fn fixed(policy: OverflowPolicy) -> Self {
+        Self {
+            samples: SampleRing::default(),
+            capacity: N,
+            policy,
+            dropped: 0,
+        }
+    }
+}
+impl<S: SampleStorage> RateSampleHistory<S> {
+    pub fn push(&mut self, sample: RateSample) {
+        if self.samples.len() < self.capacity {
+            self.samples.push_back(sample);
+            return;
+        }
+        self.dropped += 1;
+        match self.policy {
+            OverflowPolicy::DropOldest => {
+                if self.samples.pop_front().is_some() {
+                    self.samples.push_back(sample);
+                }
+            }
+            OverflowPolicy::DropNewest => (),
+        }
+    }
+
+    /// Oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &RateSample> {
+        self.samples.iter()
+    }
+
+    /// The number of samples discarded because of the capacity
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    fn fill<S: SampleStorage>(mut history: RateSampleHistory<S>) -> RateSampleHistory<S> {
+        let now = Instant::now();
+        for rate in 1..=5 {
+            history.push(RateSample::synthetic(
+                rate as f64,
+                false,
+                now,
+                Duration::from_secs(1),
+            ));
+        }
+        history
+    }
+
+    fn rates<S: SampleStorage>(history: &RateSampleHistory<S>) -> Vec<f64> {
+        history.iter().map(|rs| rs.delivery_rate_raw()).collect()
+    }
+
+    #[test]
+    fn test_drop_oldest() {
+        let policy = OverflowPolicy::DropOldest;
+        let history = fill(RateSampleHistory::new(3, policy));
+        assert_eq!(rates(&history), [3., 4., 5.]);
+        assert_eq!(history.dropped(), 2);
+        let history = fill(FixedRateSampleHistory::<3>::fixed(policy));
+        assert_eq!(rates(&history), [3., 4., 5.]);
+        assert_eq!(history.dropped(), 2);
+    }
+
+    #[test]
+    fn test_drop_newest() {
+        let policy = OverflowPolicy::DropNewest;
+        let history = fill(RateSampleHistory::new(3, policy));
+        assert_eq!(rates(&history), [1., 2., 3.]);
+        assert_eq!(history.dropped(), 2);
+        let history = fill(FixedRateSampleHistory::<3>::fixed(policy));
+        assert_eq!(rates(&history), [1., 2., 3.]);
+        assert_eq!(history.dropped(), 2);
+    }
+}
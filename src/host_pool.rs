@@ -0,0 +1,240 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{destination_cache::DestKey, RateSample};
+
+#[derive(Debug, Clone)]
+pub struct HostPoolConfig {
+    /// A connection's samples older than this no longer count toward its contribution
+    pub window: Duration,
+}
+impl Default for HostPoolConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(10),
+        }
+    }
+}
+
+/// The pooled estimates of a host
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HostEstimate {
+    /// The sum of the contributing connections' windowed max delivery rates, i.e. the capacity they observed together
+    ///
+    /// Measured in octets or packets per second
+    pub bandwidth: f64,
+    /// The smallest RTT any contributing connection reported
+    pub min_rtt: Option<Duration>,
+    /// The number of connections with a sample in the window
+    pub connections: usize,
+}
+impl HostEstimate {
+    /// An even split of [`Self::bandwidth`] once one more connection joins, e.g. to start a new connection at
+    pub fn fair_share_for_new(&self) -> f64 {
+        self.bandwidth / (self.connections + 1) as f64
+    }
+}
+
+/// Rate samples pooled from parallel connections to the same host, so a new or application-limited connection need not rediscover the shared bottleneck
+///
+/// Each connection keeps its own estimators for its own control; the pool is advisory.
+///
+/// Contribution weighting: every connection contributes exactly one rate, the max over its samples within [`HostPoolConfig::window`], however many samples it produced, so a busy connection does not mask another's data.
+/// Summing the contributions assumes the connections run concurrently through the same bottleneck, each observing its share of it.
+///
+/// Connections are told apart by a connection id of the caller's choosing, unique per host; a connection whose samples all left the window stops contributing and is forgotten.
+/// It is safe to share among connections on different threads.
+#[derive(Debug)]
+pub struct HostPool {
+    config: HostPoolConfig,
+    hosts: Mutex<HashMap<DestKey, HashMap<u64, Contribution>>>,
+}
+impl HostPool {
+    pub fn new(config: HostPoolConfig) -> Self {
+        Self {
+            config,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Upon the connection `connection` to `key` producing `sample`
+    ///
+    /// Unreliable samples are ignored.
+    /// `min_rtt`: the connection's current min RTT; zero if unknown.
+    pub fn on_sample(
+        &self,
+        key: DestKey,
+        connection: u64,
+        sample: &RateSample,
+        min_rtt: Duration,
+        now: Instant,
+    ) {
+        if !sample.is_reliable() {
+            return;
+        }
+        let mut hosts = self.hosts.lock().unwrap();
+        let contribution = hosts.entry(key).or_default().entry(connection).or_default();
+        contribution.expire(now, self.config.window);
+        // Monotonic deque: a sample no larger than a newer one can never be the max again
+        let rate = sample.delivery_rate_raw();
+        while contribution
+            .maxima
+            .back()
+            .is_some_and(|(_, max)| *max <= rate)
+        {
+            contribution.maxima.pop_back();
+        }
+        contribution.maxima.push_back((now, rate));
+        if !min_rtt.is_zero() {
+            contribution.min_rtt = Some(contribution.min_rtt.map_or(min_rtt, |m| m.min(min_rtt)));
+        }
+    }
+
+    /// Upon the connection `connection` to `key` closing
+    pub fn remove(&self, key: DestKey, connection: u64) {
+        let mut hosts = self.hosts.lock().unwrap();
+        let Some(host) = hosts.get_mut(&key) else {
+            return;
+        };
+        host.remove(&connection);
+        if host.is_empty() {
+            hosts.remove(&key);
+        }
+    }
+
+    /// [`None`] if no connection to `key` has a sample in the window
+    ///
+    /// Connections without a sample in the window are forgotten, as is the host once none is left.
+    pub fn estimate(&self, key: DestKey, now: Instant) -> Option<HostEstimate> {
+        let mut hosts = self.hosts.lock().unwrap();
+        let host = hosts.get_mut(&key)?;
+        host.retain(|_, contribution| {
+            contribution.expire(now, self.config.window);
+            !contribution.maxima.is_empty()
+        });
+        if host.is_empty() {
+            hosts.remove(&key);
+            return None;
+        }
+        let mut estimate = HostEstimate {
+            bandwidth: 0.,
+            min_rtt: None,
+            connections: 0,
+        };
+        for contribution in host.values() {
+            let Some((_, max)) = contribution.maxima.front() else {
+                continue;
+            };
+            estimate.bandwidth += max;
+            estimate.connections += 1;
+            if let Some(rtt) = contribution.min_rtt {
+                estimate.min_rtt = Some(estimate.min_rtt.map_or(rtt, |min| min.min(rtt)));
+            }
+        }
+        Some(estimate)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Contribution {
+    /// (time, rate) with decreasing rates, the front being the windowed max
+    maxima: VecDeque<(Instant, f64)>,
+    min_rtt: Option<Duration>,
+}
+impl Contribution {
+    fn expire(&mut self, now: Instant, window: Duration) {
+        while self
+            .maxima
+            .front()
+            .is_some_and(|(t, _)| window < now.saturating_duration_since(*t))
+        {
+            self.maxima.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::ConnectionState;
+
+    /// Two connections sharing a 100_000 octets/s bottleneck evenly: each sends 1_000 octets every 20 ms over `rtt`
+    fn connection(pool: &HostPool, key: DestKey, id: u64, rtt: Duration, start: Instant) {
+        let ms = Duration::from_millis(1);
+        let mut c = ConnectionState::new(start);
+        let mut in_flight = VecDeque::new();
+        for k in 0..200 {
+            let now = start + k * 20 * ms;
+            while in_flight
+                .front()
+                .is_some_and(|(sent, _)| *sent + rtt <= now)
+            {
+                let (_, p) = in_flight.pop_front().unwrap();
+                if let Some(rs) = c.sample_rate_iter([(p, 1_000)], now, rtt) {
+                    pool.on_sample(key, id, &rs, rtt, now);
+                }
+            }
+            in_flight.push_back((now, c.send_packet_2(now, in_flight.is_empty())));
+        }
+    }
+
+    #[test]
+    fn test_shared_bottleneck() {
+        let start = Instant::now();
+        let ms = Duration::from_millis(1);
+        let pool = Arc::new(HostPool::new(HostPoolConfig::default()));
+        let key = DestKey(1);
+        assert_eq!(pool.estimate(key, start), None);
+
+        let threads = [(1, 60 * ms), (2, 40 * ms)].map(|(id, rtt)| {
+            let pool = Arc::clone(&pool);
+            std::thread::spawn(move || connection(&pool, key, id, rtt, start))
+        });
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let now = start + 4_000 * ms;
+        let estimate = pool.estimate(key, now).unwrap();
+        assert_eq!(estimate.connections, 2);
+        assert!(
+            (estimate.bandwidth - 100_000.).abs() < 1_000.,
+            "{estimate:?}"
+        );
+        assert_eq!(estimate.min_rtt, Some(40 * ms));
+        assert!((estimate.fair_share_for_new() - 33_333.).abs() < 1_000.);
+        assert_eq!(pool.estimate(DestKey(2), now), None);
+
+        pool.remove(key, 2);
+        let estimate = pool.estimate(key, now).unwrap();
+        assert_eq!(estimate.connections, 1);
+        assert!((estimate.bandwidth - 50_000.).abs() < 500., "{estimate:?}");
+        assert_eq!(estimate.min_rtt, Some(60 * ms));
+
+        // Expired
+        assert_eq!(pool.estimate(key, now + Duration::from_secs(60)), None);
+        assert!(pool.hosts.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_expired_connection_pruned() {
+        let start = Instant::now();
+        let s = Duration::from_secs(1);
+        let pool = HostPool::new(HostPoolConfig::default());
+        let key = DestKey(1);
+        let sample = |rate| RateSample::synthetic(rate, false, start, s);
+        pool.on_sample(key, 1, &sample(10_000.), s, start);
+        pool.on_sample(key, 2, &sample(20_000.), s, start + 5 * s);
+        // Connection 1 went quiet without being removed
+        let estimate = pool.estimate(key, start + 12 * s).unwrap();
+        assert_eq!(estimate.connections, 1);
+        assert_eq!(estimate.bandwidth, 20_000.);
+        let hosts = pool.hosts.lock().unwrap();
+        assert_eq!(hosts[&key].len(), 1);
+    }
+}
@@ -0,0 +1,120 @@
+//! The pure arithmetic behind [`crate::ConnectionState`], free of clocks and connection state
+//!
+//! Durations are [`core::time::Duration`]; nothing here reads an [`std::time::Instant`].
+
+use core::time::Duration;
+
+/// The interval over which the newest packet's data was delivered: the longer of `send_elapsed` and `ack_elapsed`
+///
+/// Taking the longer one keeps `ACK` compression (short `ack_elapsed`) and send bursts (short `send_elapsed`) from overestimating the rate.
+pub fn sampling_interval(send_elapsed: Duration, ack_elapsed: Duration) -> Duration {
+    send_elapsed.max(ack_elapsed)
+}
+
+/// Floor `interval` to one clock `tick`
+///
+/// Return the floored interval and whether it was raised.
+pub fn floor_to_tick(interval: Duration, tick: Option<Duration>) -> (Duration, bool) {
+    match tick {
+        Some(tick) if interval < tick => (tick, true),
+        _ => (interval, false),
+    }
+}
+
+/// Whether a sample over `interval` spans at least `min_rtt`, give or take `tolerance`
+///
+/// A zero `min_rtt` disables the check.
+pub fn is_reliable(min_rtt: Duration, interval: Duration, tolerance: Duration) -> bool {
+    min_rtt.is_zero() || min_rtt <= interval + tolerance
+}
+
+/// `delivered` per second over `interval`
+///
+/// [`None`] for a zero `interval`.
+pub fn rate(delivered: u64, interval: Duration) -> Option<f64> {
+    if interval.is_zero() {
+        return None;
+    }
+    Some(delivered as f64 / interval.as_secs_f64())
+}
+
+/// The transport send buffer has less than `SMSS` of unsent data available to send
+pub fn few_data_to_send(write_seq: u64, nxt: u64, mss: u64) -> bool {
+    write_seq.saturating_sub(nxt) < mss
+}
+
+/// The amount of data in flight is less than the congestion window, both in the same unit
+pub fn cwnd_not_full(pipe: u64, wnd: u64) -> bool {
+    pipe < wnd
+}
+
+/// The peer's receive window has no room for one more full-sized segment beyond the `in_flight` sequence space
+pub fn rwnd_limited(rwnd: u64, in_flight: u64, mss: u64) -> bool {
+    rwnd < in_flight.saturating_add(mss)
+}
+
+/// The application-limited watermark: the index of the last packet transmitted so far
+pub fn app_limited_watermark(delivered: u64, pipe: u64) -> u64 {
+    delivered + pipe
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sampling_interval() {
+        let ms = Duration::from_millis(1);
+        assert_eq!(sampling_interval(10 * ms, 20 * ms), 20 * ms);
+        assert_eq!(sampling_interval(20 * ms, 10 * ms), 20 * ms);
+        assert_eq!(
+            sampling_interval(Duration::ZERO, Duration::ZERO),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn test_floor_to_tick() {
+        let ms = Duration::from_millis(1);
+        assert_eq!(floor_to_tick(Duration::ZERO, Some(ms)), (ms, true));
+        assert_eq!(floor_to_tick(ms, Some(ms)), (ms, false));
+        assert_eq!(floor_to_tick(2 * ms, Some(ms)), (2 * ms, false));
+        assert_eq!(floor_to_tick(Duration::ZERO, None), (Duration::ZERO, false));
+    }
+
+    #[test]
+    fn test_is_reliable() {
+        let ms = Duration::from_millis(1);
+        assert!(is_reliable(Duration::ZERO, Duration::ZERO, Duration::ZERO));
+        assert!(is_reliable(10 * ms, 10 * ms, Duration::ZERO));
+        assert!(!is_reliable(10 * ms, 9 * ms, Duration::ZERO));
+        assert!(is_reliable(10 * ms, 9 * ms, ms));
+    }
+
+    #[test]
+    fn test_rate() {
+        let ms = Duration::from_millis(1);
+        assert_eq!(rate(1_000, 100 * ms), Some(10_000.));
+        assert_eq!(rate(0, 100 * ms), Some(0.));
+        assert_eq!(rate(1_000, Duration::ZERO), None);
+    }
+
+    #[test]
+    fn test_app_limited_conditions() {
+        assert!(few_data_to_send(100, 100, 1_460));
+        assert!(few_data_to_send(1_559, 100, 1_460));
+        assert!(!few_data_to_send(1_560, 100, 1_460));
+        // `nxt` past `write_seq`: nothing unsent
+        assert!(few_data_to_send(100, 200, 1_460));
+
+        assert!(cwnd_not_full(9, 10));
+        assert!(!cwnd_not_full(10, 10));
+
+        assert!(!rwnd_limited(2_920, 1_460, 1_460));
+        assert!(rwnd_limited(2_919, 1_460, 1_460));
+        assert!(rwnd_limited(0, u64::MAX, 1_460));
+
+        assert_eq!(app_limited_watermark(1_000, 500), 1_500);
+        assert_eq!(app_limited_watermark(1_000, 0), 1_000);
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::destination_cache::CachedEstimate;
+
+/// Windowed min-RTT over a short and a long window, updated together
+///
+/// Typical windows:
+/// - short (e.g. 200 ms): reacts within a few round trips, for quick queue detection;
+/// - long (e.g. 10 s, the `min_rtt` window of BBR): the baseline propagation delay of the path.
+///
+/// Both minimums come from the same sample history, so they never disagree about the samples seen.
+#[derive(Debug, Clone)]
+pub struct DualMinRtt {
+    short: Duration,
+    long: Duration,
+    /// `(sampled at, RTT)`, increasing in both; the front is the minimum of any suffix of the history
+    samples: VecDeque<(Instant, Duration)>,
+    /// `(expires at, RTT)` of the [`Self::warm_start()`] prior
+    prior: Option<(Instant, Duration)>,
+}
+impl DualMinRtt {
+    pub fn new(short: Duration, long: Duration) -> Self {
+        Self {
+            short,
+            long: long.max(short),
+            samples: VecDeque::new(),
+            prior: None,
+        }
+    }
+
+    /// Seed the long window with a cached min RTT, e.g. from a [`DestinationCache`](crate::destination_cache::DestinationCache), expiring after `expiry` rather than the long window
+    ///
+    /// A shorter expiry lets a path whose propagation delay grew since the estimate was cached correct the baseline sooner.
+    /// The short window only ever reflects measured samples.
+    pub fn warm_start(&mut self, prior: &CachedEstimate, expiry: Duration, now: Instant) {
+        self.prior = Some((now + expiry.min(self.long), prior.min_rtt));
+    }
+
+    /// Whether [`Self::long_min_rtt()`] at `now` is the [`Self::warm_start()`] prior rather than a measured sample
+    pub fn is_provisional(&self, now: Instant) -> bool {
+        let Some(prior) = self.live_prior(now) else {
+            return false;
+        };
+        self.min_within(self.long, now)
+            .is_none_or(|measured| prior < measured)
+    }
+
+    fn live_prior(&self, now: Instant) -> Option<Duration> {
+        self.prior
+            .filter(|(expires_at, _)| now < *expires_at)
+            .map(|(_, rtt)| rtt)
+    }
+
+    pub fn on_rtt_sample(&mut self, rtt: Duration, now: Instant) {
+        while self.samples.back().is_some_and(|(_, r)| rtt <= *r) {
+            self.samples.pop_back();
+        }
+        self.samples.push_back((now, rtt));
+        while self
+            .samples
+            .front()
+            .is_some_and(|(t, _)| self.long < now.saturating_duration_since(*t))
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// The minimum RTT sampled within the short window before `now`
+    pub fn short_min_rtt(&self, now: Instant) -> Option<Duration> {
+        self.min_within(self.short, now)
+    }
+
+    /// The minimum RTT sampled within the long window before `now`
+    pub fn long_min_rtt(&self, now: Instant) -> Option<Duration> {
+        let measured = self.min_within(self.long, now);
+        match (measured, self.live_prior(now)) {
+            (Some(measured), Some(prior)) => Some(measured.min(prior)),
+            (measured, prior) => measured.or(prior),
+        }
+    }
+
+    fn min_within(&self, window: Duration, now: Instant) -> Option<Duration> {
+        self.samples
+            .iter()
+            .find(|(t, _)| now.saturating_duration_since(*t) <= window)
+            .map(|(_, rtt)| *rtt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_reacts_faster() {
+        let now = Instant::now();
+        let ms = Duration::from_millis(1);
+        let mut min_rtt = DualMinRtt::new(200 * ms, 10_000 * ms);
+        min_rtt.on_rtt_sample(40 * ms, now);
+        // A queue builds up
+        for i in 1..=10 {
+            min_rtt.on_rtt_sample(80 * ms, now + i * 50 * ms);
+        }
+        let t = now + 500 * ms;
+        assert_eq!(min_rtt.short_min_rtt(t), Some(80 * ms));
+        assert_eq!(min_rtt.long_min_rtt(t), Some(40 * ms));
+
+        // The queue drains
+        min_rtt.on_rtt_sample(50 * ms, t);
+        assert_eq!(min_rtt.short_min_rtt(t), Some(50 * ms));
+        assert_eq!(min_rtt.long_min_rtt(t), Some(40 * ms));
+
+        // The old baseline leaves the long window
+        let t = now + 10_001 * ms;
+        assert_eq!(min_rtt.short_min_rtt(t), None);
+        assert_eq!(min_rtt.long_min_rtt(t), Some(50 * ms));
+    }
+
+    #[test]
+    fn test_warm_start() {
+        let now = Instant::now();
+        let ms = Duration::from_millis(1);
+        let prior = CachedEstimate {
+            bandwidth: 100_000.,
+            min_rtt: 40 * ms,
+            updated_at: now,
+        };
+        let mut min_rtt = DualMinRtt::new(200 * ms, 10_000 * ms);
+        min_rtt.warm_start(&prior, 1_000 * ms, now);
+        assert_eq!(min_rtt.long_min_rtt(now), Some(40 * ms));
+        assert_eq!(min_rtt.short_min_rtt(now), None);
+        assert!(min_rtt.is_provisional(now));
+
+        // The path got longer; the prior expires early
+        min_rtt.on_rtt_sample(60 * ms, now + 100 * ms);
+        assert_eq!(min_rtt.long_min_rtt(now + 100 * ms), Some(40 * ms));
+        assert_eq!(min_rtt.long_min_rtt(now + 1_000 * ms), Some(60 * ms));
+        assert!(!min_rtt.is_provisional(now + 1_000 * ms));
+
+        // A measured sample below the prior takes over at once
+        let mut min_rtt = DualMinRtt::new(200 * ms, 10_000 * ms);
+        min_rtt.warm_start(&prior, 1_000 * ms, now);
+        min_rtt.on_rtt_sample(30 * ms, now);
+        assert_eq!(min_rtt.long_min_rtt(now), Some(30 * ms));
+        assert!(!min_rtt.is_provisional(now));
+    }
+}
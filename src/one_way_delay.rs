@@ -0,0 +1,130 @@
+use std::{collections::VecDeque, time::Instant};
+
+use crate::{delay_gradient::DelayTrend, PacketState};
+
+#[derive(Debug, Clone)]
+pub struct OneWayDelayConfig {
+    /// The number of most recent packets the trend is fitted over
+    pub window: usize,
+    /// A slope within `±threshold` is [`DelayTrend::Stable`]
+    ///
+    /// Measured in seconds of one-way delay change per second of sending, e.g. `0.01` for 1 ms more delay every 100 ms.
+    pub threshold: f64,
+}
+impl Default for OneWayDelayConfig {
+    fn default() -> Self {
+        Self {
+            window: 32,
+            threshold: 0.01,
+        }
+    }
+}
+
+/// The trend of the one-way delay, PCC/Copa-style: the least-squares slope of the one-way delay against the send time over the last few packets
+///
+/// Each packet pairs its [`PacketState::sent_time()`] with a receive timestamp carried back by the protocol (e.g. a QUIC timestamp extension).
+///
+/// # Clock offset
+///
+/// The receive timestamps come from the receiver's clock, whose offset from the sender's is unknown, so the one-way delays computed here are off by that constant and may even be negative.
+/// Only their trend is meaningful, and only as long as the clocks run at the same rate: a clock skew shows up as a constant slope.
+#[derive(Debug, Clone)]
+pub struct OneWayDelayTracker {
+    config: OneWayDelayConfig,
+    /// The first send time seen, which the send times are measured from
+    origin: Option<Instant>,
+    /// (send time, one-way delay) in seconds, oldest first
+    points: VecDeque<(f64, f64)>,
+}
+impl OneWayDelayTracker {
+    pub fn new(config: OneWayDelayConfig) -> Self {
+        Self {
+            config,
+            origin: None,
+            points: VecDeque::new(),
+        }
+    }
+
+    /// Upon an `ACK` carrying the time `packet` was received
+    ///
+    /// `receive_timestamp`: in seconds on the receiver's clock, from any epoch fixed for the connection
+    pub fn on_packet(&mut self, packet: &PacketState, receive_timestamp: f64) {
+        let sent_time = packet.sent_time();
+        let origin = *self.origin.get_or_insert(sent_time);
+        let sent = sent_time.saturating_duration_since(origin).as_secs_f64();
+        self.points.push_back((sent, receive_timestamp - sent));
+        while self.config.window.max(2) < self.points.len() {
+            self.points.pop_front();
+        }
+    }
+
+    /// The fitted change of the one-way delay per second of sending; [`None`] until two packets with different send times arrived
+    pub fn slope(&self) -> Option<f64> {
+        let n = self.points.len() as f64;
+        let mean_x = self.points.iter().map(|(x, _)| x).sum::<f64>() / n;
+        let mean_y = self.points.iter().map(|(_, y)| y).sum::<f64>() / n;
+        let (cov, var) = self.points.iter().fold((0., 0.), |(cov, var), (x, y)| {
+            let dx = x - mean_x;
+            (cov + dx * (y - mean_y), var + dx * dx)
+        });
+        (var != 0.).then(|| cov / var)
+    }
+
+    /// Classified from [`Self::slope()`]
+    pub fn trend(&self) -> Option<DelayTrend> {
+        let slope = self.slope()?;
+        let threshold = self.config.threshold;
+        Some(if threshold < slope {
+            DelayTrend::QueueBuilding
+        } else if slope < -threshold {
+            DelayTrend::Draining
+        } else {
+            DelayTrend::Stable
+        })
+    }
+
+    /// Forget every packet, e.g. after a path change
+    pub fn reset(&mut self) {
+        self.origin = None;
+        self.points.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::ConnectionState;
+
+    #[test]
+    fn test_increasing_delay() {
+        let now = Instant::now();
+        let ms = Duration::from_millis(1);
+        let mut c = ConnectionState::new(now);
+        let mut owd = OneWayDelayTracker::new(OneWayDelayConfig::default());
+        assert_eq!(owd.trend(), None);
+
+        // The receiver's clock is 1000 s ahead; the queue grows by 0.5 ms per 10 ms packet
+        let offset = 1_000.;
+        for k in 0..64 {
+            let sent = k * 10 * ms;
+            let p = c.send_packet_2(now + sent, k == 0);
+            let delay = 0.020 + 0.0005 * k as f64;
+            owd.on_packet(&p, offset + sent.as_secs_f64() + delay);
+        }
+        let slope = owd.slope().unwrap();
+        assert!((slope - 0.05).abs() < 1e-9, "{slope}");
+        assert_eq!(owd.trend(), Some(DelayTrend::QueueBuilding));
+
+        // A constant delay, whatever the offset
+        owd.reset();
+        for k in 64..128 {
+            let sent = k * 10 * ms;
+            let p = c.send_packet_2(now + sent, false);
+            owd.on_packet(&p, -5. + sent.as_secs_f64() + 0.020);
+        }
+        assert!(owd.slope().unwrap().abs() < 1e-9);
+        assert_eq!(owd.trend(), Some(DelayTrend::Stable));
+    }
+}
@@ -0,0 +1,278 @@
+use std::{collections::BTreeMap, ops::RangeInclusive, time::Instant};
+
+use crate::{ConnectionState, PacketState};
+
+/// A sent-packet store keyed by packet number, holding each packet's state and data length
+pub type PacketMap = BTreeMap<u64, (PacketState, u64)>;
+
+/// Remove the packets acknowledged by `ranges` from `store`, yielding them by value in packet number order
+///
+/// Feed the iterator to [`crate::ConnectionState::sample_rate_iter()`] to sample without collecting the packets.
+/// - Packet numbers missing from `store` (e.g. already declared lost) are skipped.
+/// - No heap allocation happens beyond the removals from `store`.
+/// - Entries are removed as they are yielded; dropping the iterator early leaves the rest in `store`.
+pub fn drain_acked<'a>(
+    store: &'a mut PacketMap,
+    ranges: &'a [RangeInclusive<u64>],
+) -> impl Iterator<Item = (PacketState, u64)> + 'a {
+    let start = ranges.iter().map(|r| *r.start()).min().unwrap_or(1);
+    let end = ranges.iter().map(|r| *r.end()).max().unwrap_or(0);
+    store
+        .extract_if(start..=end, |pkt_num, _| {
+            ranges.iter().any(|r| r.contains(pkt_num))
+        })
+        .map(|(_, packet)| packet)
+}
+
+/// A reference to a packet in a [`PacketSlab`]
+///
+/// The generation tells a handle to a packet from a stale handle to an earlier packet in the same slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PacketHandle {
+    index: u32,
+    generation: u32,
+}
+
+/// How a full [`PacketSlab`] grows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SlabGrowth {
+    /// Double the capacity, amortizing the copies
+    #[default]
+    Double,
+    /// Add this many slots, bounding the memory overshoot
+    Linear(usize),
+}
+
+#[derive(Debug, Clone)]
+enum Slot {
+    Occupied {
+        generation: u32,
+        packet: (PacketState, u64),
+    },
+    Vacant {
+        generation: u32,
+        next_free: Option<u32>,
+    },
+}
+
+/// A sent-packet store resolving [`PacketHandle`]s by index, with no hashing or tree walks
+///
+/// Vacated slots are reused last-in first-out, each reuse bumping the slot's generation so that stale handles resolve to [`None`].
+/// Prefer it over [`PacketMap`] with many packets in flight when the caller can keep the handle (e.g. in its own per-packet metadata).
+#[derive(Debug, Clone, Default)]
+pub struct PacketSlab {
+    slots: Vec<Slot>,
+    free: Option<u32>,
+    len: usize,
+    growth: SlabGrowth,
+}
+impl PacketSlab {
+    pub fn new(growth: SlabGrowth) -> Self {
+        Self {
+            slots: Vec::new(),
+            free: None,
+            len: 0,
+            growth,
+        }
+    }
+
+    pub fn with_capacity(capacity: usize, growth: SlabGrowth) -> Self {
+        Self {
+            slots: Vec::with_capacity(capacity),
+            ..Self::new(growth)
+        }
+    }
+
+    /// # Panics
+    ///
+    /// If the slab would hold more than `u32::MAX` slots
+    pub fn insert(&mut self, state: PacketState, data_length: u64) -> PacketHandle {
+        let packet = (state, data_length);
+        self.len += 1;
+        if let Some(index) = self.free {
+            let slot = &mut self.slots[index as usize];
+            let Slot::Vacant {
+                generation,
+                next_free,
+            } = *slot
+            else {
+                unreachable!("free list points to an occupied slot");
+            };
+            self.free = next_free;
+            *slot = Slot::Occupied { generation, packet };
+            return PacketHandle { index, generation };
+        }
+        let index = u32::try_from(self.slots.len()).expect("too many slots");
+        if self.slots.len() == self.slots.capacity() {
+            let additional = match self.growth {
+                SlabGrowth::Double => self.slots.len().max(1),
+                SlabGrowth::Linear(n) => n.max(1),
+            };
+            self.slots.reserve_exact(additional);
+        }
+        self.slots.push(Slot::Occupied {
+            generation: 0,
+            packet,
+        });
+        PacketHandle {
+            index,
+            generation: 0,
+        }
+    }
+
+    /// Snapshot a packet being sent from `connection` and store it
+    pub fn send_packet(
+        &mut self,
+        connection: &mut ConnectionState,
+        send_time: Instant,
+        no_packets_in_flight: bool,
+        data_length: u64,
+    ) -> PacketHandle {
+        let state = connection.send_packet_2(send_time, no_packets_in_flight);
+        self.insert(state, data_length)
+    }
+
+    /// [`None`] if `handle` is stale, i.e. its packet was already taken
+    pub fn get(&self, handle: PacketHandle) -> Option<&(PacketState, u64)> {
+        match self.slots.get(handle.index as usize)? {
+            Slot::Occupied { generation, packet } if *generation == handle.generation => {
+                Some(packet)
+            }
+            _ => None,
+        }
+    }
+
+    /// Remove the packet; [`None`] if `handle` is stale
+    pub fn take(&mut self, handle: PacketHandle) -> Option<(PacketState, u64)> {
+        let slot = self.slots.get_mut(handle.index as usize)?;
+        match slot {
+            Slot::Occupied { generation, .. } if *generation == handle.generation => {}
+            _ => return None,
+        }
+        let vacant = Slot::Vacant {
+            generation: handle.generation.wrapping_add(1),
+            next_free: self.free,
+        };
+        let Slot::Occupied { packet, .. } = std::mem::replace(slot, vacant) else {
+            unreachable!();
+        };
+        self.free = Some(handle.index);
+        self.len -= 1;
+        Some(packet)
+    }
+
+    /// Remove the packets of `handles`, yielding them by value in the order of `handles`
+    ///
+    /// Feed the iterator to [`crate::ConnectionState::sample_rate_iter()`]; stale handles are skipped.
+    pub fn drain<'a>(
+        &'a mut self,
+        handles: &'a [PacketHandle],
+    ) -> impl Iterator<Item = (PacketState, u64)> + 'a {
+        handles.iter().filter_map(|handle| self.take(*handle))
+    }
+
+    /// The number of packets stored
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of slots allocated
+    pub fn capacity(&self) -> usize {
+        self.slots.capacity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::ConnectionState;
+
+    #[test]
+    fn test_partially_missing_ranges() {
+        let now = Instant::now();
+        let mut c = ConnectionState::new(now);
+        let mut store = PacketMap::new();
+        for pkt_num in 0..10 {
+            store.insert(pkt_num, (c.send_packet_2(now, pkt_num == 0), 1));
+        }
+        // Lost
+        store.remove(&3);
+        store.remove(&4);
+
+        let ranges = [2..=5, 7..=7, 9..=12];
+        let drained = drain_acked(&mut store, &ranges).count();
+        assert_eq!(drained, 4);
+        assert_eq!(store.keys().copied().collect::<Vec<_>>(), [0, 1, 6, 8]);
+
+        let ranges = [0..=u64::MAX];
+        c.sample_rate_iter(
+            drain_acked(&mut store, &ranges),
+            now + Duration::from_secs(1),
+            Duration::ZERO,
+        );
+        assert!(store.is_empty());
+        assert_eq!(c.reconcile_delivered(4), 0);
+    }
+
+    #[test]
+    fn test_slab_stale_handles() {
+        let now = Instant::now();
+        let mut c = ConnectionState::new(now);
+        let mut slab = PacketSlab::default();
+        let a = slab.send_packet(&mut c, now, true, 1_000);
+        let b = slab.send_packet(&mut c, now, false, 1_000);
+        assert_eq!(slab.len(), 2);
+
+        assert_eq!(slab.take(a).unwrap().1, 1_000);
+        assert!(slab.take(a).is_none());
+        assert!(slab.get(a).is_none());
+
+        // `a`'s slot is reused under a new generation
+        let c2 = slab.send_packet(&mut c, now, false, 500);
+        assert_ne!(a, c2);
+        assert!(slab.get(a).is_none());
+        assert_eq!(slab.get(c2).unwrap().1, 500);
+        assert_eq!(slab.len(), 2);
+
+        let handles = [a, b, c2];
+        let drained = slab.drain(&handles).map(|(_, len)| len).collect::<Vec<_>>();
+        assert_eq!(drained, [1_000, 500]);
+        assert!(slab.is_empty());
+    }
+
+    #[test]
+    fn test_slab_growth() {
+        let now = Instant::now();
+        let mut c = ConnectionState::new(now);
+        let mut slab = PacketSlab::with_capacity(4, SlabGrowth::Linear(4));
+        for k in 0..5 {
+            slab.send_packet(&mut c, now, k == 0, 1);
+        }
+        assert_eq!(slab.capacity(), 8);
+
+        let mut slab = PacketSlab::with_capacity(4, SlabGrowth::Double);
+        for k in 0..5 {
+            slab.send_packet(&mut c, now, k == 0, 1);
+        }
+        assert_eq!(slab.capacity(), 8);
+        for k in 0..4 {
+            slab.send_packet(&mut c, now, k == 0, 1);
+        }
+        assert_eq!(slab.capacity(), 16);
+
+        // Vacated slots are reused before growing
+        let mut linear = PacketSlab::with_capacity(4, SlabGrowth::Linear(4));
+        let first = linear.send_packet(&mut c, now, false, 1);
+        linear.take(first);
+        for _ in 0..4 {
+            linear.send_packet(&mut c, now, false, 1);
+        }
+        assert_eq!(linear.capacity(), 4);
+    }
+}
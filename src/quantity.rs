@@ -0,0 +1,114 @@
+//! Typed amounts of data
+//!
+//! For amounts whose unit is known statically, e.g. [`crate::TransportSendSequenceSpace::mss_bytes()`] and [`crate::ConnectionState::delivered_as()`].
+//!
+//! `data_length`, `delivered`, `wnd`, and `pipe` stay `u64`: their unit is chosen at runtime by [`crate::DeliveryRateConfig::unit`], so no single type fits them,
+//! and `pipe` and `wnd` share that unit, so a newtype could not tell one passed for the other anyway.
+//!
+//! Mixing units does not compile:
+//!
+//! ```compile_fail
+//! use dre::quantity::{Bytes, Packets};
+//! let pipe = Bytes(2_400);
+//! let wnd = Packets(10);
+//! let cwnd_not_full = pipe < wnd;
+//! ```
+//!
+//! ```compile_fail
+//! use dre::quantity::{Bytes, Packets};
+//! let delivered = Bytes(2_400) + Packets(1);
+//! ```
+
+use std::{
+    iter::Sum,
+    ops::{Add, AddAssign, Sub, SubAssign},
+};
+
+use crate::Unit;
+
+/// An amount of data measured in a known [`Unit`]
+pub trait Quantity: Copy + Ord + From<u64> + Into<u64> {
+    const UNIT: Unit;
+}
+
+macro_rules! quantity {
+    ($(#[$attr:meta])* $name:ident, $unit:expr) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+        pub struct $name(pub u64);
+        impl Quantity for $name {
+            const UNIT: Unit = $unit;
+        }
+        impl From<u64> for $name {
+            fn from(value: u64) -> Self {
+                Self(value)
+            }
+        }
+        impl From<$name> for u64 {
+            fn from(value: $name) -> Self {
+                value.0
+            }
+        }
+        impl Add for $name {
+            type Output = Self;
+
+            fn add(self, rhs: Self) -> Self::Output {
+                Self(self.0 + rhs.0)
+            }
+        }
+        impl Sub for $name {
+            type Output = Self;
+
+            fn sub(self, rhs: Self) -> Self::Output {
+                Self(self.0 - rhs.0)
+            }
+        }
+        impl AddAssign for $name {
+            fn add_assign(&mut self, rhs: Self) {
+                self.0 += rhs.0;
+            }
+        }
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, rhs: Self) {
+                self.0 -= rhs.0;
+            }
+        }
+        impl Sum for $name {
+            fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+                Self(iter.map(|q| q.0).sum())
+            }
+        }
+    };
+}
+
+quantity!(
+    /// An amount of data measured in octets
+    Bytes,
+    Unit::Octets
+);
+quantity!(
+    /// An amount of data measured in packets
+    Packets,
+    Unit::Packets
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arithmetic() {
+        let mut pipe = Bytes(1_200) + Bytes(1_200);
+        pipe -= Bytes(200);
+        assert_eq!(pipe, Bytes(2_200));
+        assert!(pipe < Bytes(12_000));
+        assert_eq!(u64::from(pipe), 2_200);
+        assert_eq!(Packets::from(3) - Packets(1), Packets(2));
+        assert_eq!(
+            [Packets(1), Packets(2)].into_iter().sum::<Packets>(),
+            Packets(3)
+        );
+        assert_eq!(Bytes::UNIT, Unit::Octets);
+        assert_eq!(Packets::UNIT, Unit::Packets);
+    }
+}
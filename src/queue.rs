@@ -0,0 +1,167 @@
+use std::time::Duration;
+
+use crate::{DeliveryRate, RateSample};
+
+/// The data queued at the bottleneck beyond the bandwidth-delay product
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct QueueEstimate {
+    /// Measured in octets or packets, like the in-flight data
+    pub queued: u64,
+    /// The time the bottleneck takes to drain [`Self::queued`] at the bandwidth
+    pub delay: Duration,
+}
+
+/// The excess of `in_flight` over the BDP `bw * min_rtt`
+///
+/// - Zero, not negative, if `in_flight` is within the BDP.
+/// - [`None`] if `bw` is unmeasured (zero or not finite), since neither the BDP nor the drain time is defined.
+/// - The delay saturates at [`Duration::MAX`].
+///
+/// `in_flight` and `bw` must be measured in the same unit.
+pub fn queue_estimate(
+    in_flight: u64,
+    bw: DeliveryRate,
+    min_rtt: Duration,
+) -> Option<QueueEstimate> {
+    let bw = bw.as_bytes_per_sec();
+    if !bw.is_finite() || bw <= 0. {
+        return None;
+    }
+    let bdp = (bw * min_rtt.as_secs_f64()) as u64;
+    let queued = in_flight.saturating_sub(bdp);
+    Some(QueueEstimate {
+        queued,
+        delay: saturating_secs(queued as f64 / bw),
+    })
+}
+
+/// A drain time too long for [`Duration`] (e.g. at a tiny bandwidth) saturates at [`Duration::MAX`]
+fn saturating_secs(secs: f64) -> Duration {
+    Duration::try_from_secs_f64(secs).unwrap_or(if secs > 0. {
+        Duration::MAX
+    } else {
+        Duration::ZERO
+    })
+}
+
+/// [`queue_estimate()`] smoothed by an exponentially weighted moving average over rate samples
+#[derive(Debug, Clone)]
+pub struct SmoothedQueueEstimate {
+    /// The weight of a new estimate
+    gain: f64,
+    /// (queued, delay in seconds)
+    smoothed: Option<(f64, f64)>,
+}
+impl SmoothedQueueEstimate {
+    /// `gain`: the weight of a new estimate, in `(0, 1]`
+    ///
+    /// Panics if `gain` is outside `(0, 1]`.
+    pub fn new(gain: f64) -> Self {
+        assert!(0. < gain && gain <= 1., "gain {gain} is outside (0, 1]");
+        Self {
+            gain,
+            smoothed: None,
+        }
+    }
+
+    /// Samples without [`RateSample::flight_size_at_ack()`] or taken while `bw` is unmeasured are ignored
+    ///
+    /// `bw`: the filtered bandwidth, not the sample's own delivery rate
+    pub fn on_sample(&mut self, sample: &RateSample, bw: DeliveryRate, min_rtt: Duration) {
+        let Some(in_flight) = sample.flight_size_at_ack() else {
+            return;
+        };
+        let Some(estimate) = queue_estimate(in_flight, bw, min_rtt) else {
+            return;
+        };
+        let new = (estimate.queued as f64, estimate.delay.as_secs_f64());
+        self.smoothed = Some(match self.smoothed {
+            None => new,
+            Some((queued, delay)) => (
+                queued + self.gain * (new.0 - queued),
+                delay + self.gain * (new.1 - delay),
+            ),
+        });
+    }
+
+    /// [`None`] until a sample is taken; the queued data is rounded to the nearest unit
+    pub fn estimate(&self) -> Option<QueueEstimate> {
+        let (queued, delay) = self.smoothed?;
+        Some(QueueEstimate {
+            queued: queued.round() as u64,
+            delay: saturating_secs(delay),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::test_util::RateSampleBuilder;
+
+    #[test]
+    fn test_queue_estimate() {
+        let ms = Duration::from_millis(1);
+        let bw = DeliveryRate::from_bytes_per_sec(1_000_000.);
+        // BDP: 50_000
+        assert_eq!(
+            queue_estimate(80_000, bw, 50 * ms),
+            Some(QueueEstimate {
+                queued: 30_000,
+                delay: 30 * ms,
+            })
+        );
+        assert_eq!(
+            queue_estimate(20_000, bw, 50 * ms),
+            Some(QueueEstimate::default())
+        );
+        let unmeasured = DeliveryRate::from_bytes_per_sec(0.);
+        assert_eq!(queue_estimate(80_000, unmeasured, 50 * ms), None);
+
+        let tiny = DeliveryRate::from_bytes_per_sec(f64::MIN_POSITIVE);
+        assert_eq!(
+            queue_estimate(u64::MAX, tiny, 50 * ms),
+            Some(QueueEstimate {
+                queued: u64::MAX,
+                delay: Duration::MAX,
+            })
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_smoothed_gain_above_one() {
+        SmoothedQueueEstimate::new(2.);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_smoothed_gain_nan() {
+        SmoothedQueueEstimate::new(f64::NAN);
+    }
+
+    #[test]
+    fn test_smoothed() {
+        let ms = Duration::from_millis(1);
+        let bw = DeliveryRate::from_bytes_per_sec(1_000_000.);
+        let sample = |in_flight| {
+            RateSampleBuilder::new(Instant::now())
+                .flight_size_at_ack(in_flight)
+                .build()
+        };
+        let mut smoothed = SmoothedQueueEstimate::new(0.5);
+        smoothed.on_sample(&sample(None), bw, 50 * ms);
+        assert_eq!(smoothed.estimate(), None);
+        smoothed.on_sample(&sample(Some(90_000)), bw, 50 * ms);
+        smoothed.on_sample(&sample(Some(50_000)), bw, 50 * ms);
+        assert_eq!(
+            smoothed.estimate(),
+            Some(QueueEstimate {
+                queued: 20_000,
+                delay: 20 * ms,
+            })
+        );
+    }
+}
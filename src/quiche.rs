@@ -0,0 +1,178 @@
+//! Adapter driving [`ConnectionState`] from quiche's congestion control callbacks
+//!
+//! The input types mirror the fields of quiche's `Sent` and `Acked` that the adapter reads, so a quiche fork can convert into them without this crate depending on quiche.
+
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use crate::{ConnectionState, Packet, RateSample};
+
+/// The fields of quiche's `Sent` read by [`QuicheAdapter::on_packet_sent()`]
+#[derive(Debug, Clone, Copy)]
+pub struct Sent {
+    pub pkt_num: u64,
+    /// Measured in octets
+    pub size: usize,
+    pub time_sent: Instant,
+}
+
+/// The fields of quiche's `Acked` read by [`QuicheAdapter::on_packets_acked()`]
+#[derive(Debug, Clone, Copy)]
+pub struct Acked {
+    pub pkt_num: u64,
+}
+
+/// Delivery rate sampling for a quiche congestion controller
+///
+/// Data is measured in octets.
+#[derive(Debug, Clone)]
+pub struct QuicheAdapter {
+    state: ConnectionState,
+    /// Packet number -> packet in flight
+    sent: BTreeMap<u64, Packet>,
+    bytes_in_flight: u64,
+    latest: Option<RateSample>,
+}
+impl QuicheAdapter {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            state: ConnectionState::new(now),
+            sent: BTreeMap::new(),
+            bytes_in_flight: 0,
+            latest: None,
+        }
+    }
+
+    /// Upon quiche's `on_packet_sent`
+    pub fn on_packet_sent(&mut self, sent: &Sent) {
+        let state = self
+            .state
+            .send_packet_2(sent.time_sent, self.bytes_in_flight == 0);
+        let data_length = sent.size as u64;
+        self.bytes_in_flight += data_length;
+        self.sent
+            .insert(sent.pkt_num, Packet { state, data_length });
+    }
+
+    /// Upon quiche's `on_packets_acked`
+    ///
+    /// quiche delivers the newly acknowledged packets of an `ACK` frame in one batch, which makes up one rate sample.
+    /// Packets unknown to the adapter (e.g. already declared lost) are ignored.
+    pub fn on_packets_acked(
+        &mut self,
+        acked: &[Acked],
+        now: Instant,
+        min_rtt: Duration,
+    ) -> Option<&RateSample> {
+        let packets = acked
+            .iter()
+            .filter_map(|acked| self.sent.remove(&acked.pkt_num))
+            .collect::<Vec<_>>();
+        if packets.is_empty() {
+            return None;
+        }
+        for packet in &packets {
+            self.bytes_in_flight -= packet.data_length;
+        }
+        let sample = self.state.sample_rate(&packets, now, min_rtt)?;
+        self.latest = Some(sample);
+        self.latest.as_ref()
+    }
+
+    /// Upon quiche's `on_congestion_event`
+    ///
+    /// Lost packets leave the flight without being delivered.
+    pub fn on_congestion_event(&mut self, lost: &[u64]) {
+        for pkt_num in lost {
+            if let Some(packet) = self.sent.remove(pkt_num) {
+                self.bytes_in_flight -= packet.data_length;
+            }
+        }
+    }
+
+    /// Upon quiche reporting the connection as application-limited
+    pub fn on_app_limited(&mut self) {
+        self.state
+            .set_application_limited_phases(self.bytes_in_flight);
+    }
+
+    pub fn bytes_in_flight(&self) -> u64 {
+        self.bytes_in_flight
+    }
+
+    /// The most recent rate sample
+    pub fn latest_sample(&self) -> Option<&RateSample> {
+        self.latest.as_ref()
+    }
+
+    pub fn connection_state(&self) -> &ConnectionState {
+        &self.state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batched_acks() {
+        let now = Instant::now();
+        let ms = Duration::from_millis(1);
+        let mut a = QuicheAdapter::new(now);
+        let send = |a: &mut QuicheAdapter, pkt_num, time_sent| {
+            a.on_packet_sent(&Sent {
+                pkt_num,
+                size: 1_000,
+                time_sent,
+            })
+        };
+
+        send(&mut a, 0, now);
+        send(&mut a, 1, now);
+        assert_eq!(a.bytes_in_flight(), 2_000);
+        let acked = [Acked { pkt_num: 0 }, Acked { pkt_num: 1 }];
+        assert!(a.on_packets_acked(&acked, now + 10 * ms, ms).is_none());
+
+        for pkt_num in 2..5 {
+            send(&mut a, pkt_num, now + 10 * ms);
+        }
+        // Packet 3 is lost; the rest arrive in one batch
+        a.on_congestion_event(&[3]);
+        let acked = [
+            Acked { pkt_num: 2 },
+            Acked { pkt_num: 3 },
+            Acked { pkt_num: 4 },
+        ];
+        let rs = a.on_packets_acked(&acked, now + 20 * ms, ms).unwrap();
+        assert_eq!(rs.delivered(), 2_000);
+        assert_eq!(rs.interval(), 10 * ms);
+        assert_eq!(a.bytes_in_flight(), 0);
+        assert_eq!(a.latest_sample().unwrap().delivery_rate_raw(), 200_000.);
+    }
+
+    #[test]
+    fn test_app_limited() {
+        let now = Instant::now();
+        let ms = Duration::from_millis(1);
+        let mut a = QuicheAdapter::new(now);
+        a.on_packet_sent(&Sent {
+            pkt_num: 0,
+            size: 1_000,
+            time_sent: now,
+        });
+        a.on_packets_acked(&[Acked { pkt_num: 0 }], now + 10 * ms, ms);
+
+        a.on_app_limited();
+        a.on_packet_sent(&Sent {
+            pkt_num: 1,
+            size: 1_000,
+            time_sent: now + 10 * ms,
+        });
+        let rs = a
+            .on_packets_acked(&[Acked { pkt_num: 1 }], now + 20 * ms, ms)
+            .unwrap();
+        assert!(rs.is_app_limited());
+    }
+}
@@ -0,0 +1,193 @@
+//! Drive a [`ConnectionState`] through a recorded sequence of send and `ACK` events, e.g. extracted from a packet capture
+//!
+//! # Input schema
+//!
+//! CSV, one event per line; empty lines and lines starting with `#` are skipped:
+//!
+//! ```text
+//! time_us,event,seq,len
+//! ```
+//!
+//! - `time_us`: microseconds since the start of the capture, non-decreasing
+//! - `event`: `send` or `ack`
+//! - `seq`: an identifier of the packet (e.g. the packet number or the starting sequence number); a `send` of an outstanding `seq` is a retransmission
+//! - `len`: the data length (measured in octets or packets) of a `send`; ignored on an `ack`, which takes it from the matching `send`
+//!
+//! Consecutive `ack` lines with the same `time_us` form a single `ACK`.
+//! An `ack` of a `seq` not outstanding (e.g. a duplicate) is ignored.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::{ConnectionState, PacketState, RateSample};
+
+/// A malformed line of the input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayError {
+    /// 1-based
+    pub line: usize,
+    pub message: String,
+}
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+impl std::error::Error for ReplayError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EventKind {
+    Send,
+    Ack,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Event {
+    time: Duration,
+    kind: EventKind,
+    seq: u64,
+    len: u64,
+}
+
+fn parse_line(line: &str) -> Result<Event, String> {
+    let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    let [time, kind, seq, len] = fields[..] else {
+        return Err(format!("expected 4 fields, got {}", fields.len()));
+    };
+    let number = |name: &str, value: &str| {
+        value
+            .parse::<u64>()
+            .map_err(|e| format!("`{name}` `{value}`: {e}"))
+    };
+    let kind = match kind {
+        "send" => EventKind::Send,
+        "ack" => EventKind::Ack,
+        _ => return Err(format!("unknown event `{kind}`")),
+    };
+    Ok(Event {
+        time: Duration::from_micros(number("time_us", time)?),
+        kind,
+        seq: number("seq", seq)?,
+        len: number("len", len)?,
+    })
+}
+
+/// Replay `input` (see the [module docs](self) for the schema) and return the rate samples in order
+///
+/// - `c` should be freshly created at `start`, which is where `time_us` `0` maps to.
+/// - `min_rtt` is passed to every sample; [`Duration::ZERO`] accepts every interval.
+pub fn replay_csv(
+    c: &mut ConnectionState,
+    start: Instant,
+    input: &str,
+    min_rtt: Duration,
+) -> Result<Vec<RateSample>, ReplayError> {
+    let mut in_flight: HashMap<u64, (PacketState, u64)> = HashMap::new();
+    let mut samples = vec![];
+    let mut ack: Option<(Duration, Vec<(PacketState, u64)>)> = None;
+    let mut last_time = Duration::ZERO;
+    let flush = |c: &mut ConnectionState,
+                 samples: &mut Vec<RateSample>,
+                 ack: &mut Option<(Duration, Vec<(PacketState, u64)>)>| {
+        if let Some((time, acked)) = ack.take() {
+            samples.extend(c.sample_rate_iter(acked, start + time, min_rtt));
+        }
+    };
+    for (i, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let event = parse_line(line).map_err(|message| ReplayError {
+            line: i + 1,
+            message,
+        })?;
+        if event.time < last_time {
+            return Err(ReplayError {
+                line: i + 1,
+                message: "time went backwards".to_string(),
+            });
+        }
+        last_time = event.time;
+        if ack
+            .as_ref()
+            .is_some_and(|(time, _)| *time != event.time || event.kind != EventKind::Ack)
+        {
+            flush(c, &mut samples, &mut ack);
+        }
+        match event.kind {
+            EventKind::Send => {
+                let state = c.send_packet_2(start + event.time, in_flight.is_empty());
+                in_flight.insert(event.seq, (state, event.len));
+            }
+            EventKind::Ack => {
+                let Some(packet) = in_flight.remove(&event.seq) else {
+                    continue;
+                };
+                ack.get_or_insert_with(|| (event.time, vec![]))
+                    .1
+                    .push(packet);
+            }
+        }
+    }
+    flush(c, &mut samples, &mut ack);
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAPTURE: &str = include_str!("../testdata/capture.csv");
+
+    fn replay(input: &str) -> Result<Vec<RateSample>, ReplayError> {
+        let start = Instant::now();
+        let mut c = ConnectionState::new(start);
+        replay_csv(&mut c, start, input, Duration::ZERO)
+    }
+
+    fn series(samples: &[RateSample]) -> Vec<(f64, Duration, u64)> {
+        samples
+            .iter()
+            .map(|rs| {
+                (
+                    rs.delivery_rate().as_bytes_per_sec(),
+                    rs.interval(),
+                    rs.delivered(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_capture_deterministic() {
+        let a = series(&replay(CAPTURE).unwrap());
+        let b = series(&replay(CAPTURE).unwrap());
+        assert_eq!(a, b);
+        // 1_200 octets every 10 ms; the first flight anchors no sample
+        assert_eq!(a.len(), 5);
+        assert_eq!(a[0], (120_000., Duration::from_millis(50), 6_000));
+    }
+
+    #[test]
+    fn test_batched_ack() {
+        let input = "0,send,0,100\n0,send,1,100\n10,ack,0,0\n10,send,2,100\n10,send,3,100\n20,ack,2,0\n20,ack,3,0\n20,ack,1,0";
+        let samples = replay(input).unwrap();
+        assert_eq!(samples.len(), 1);
+        assert_eq!(samples[0].delivered(), 300);
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert_eq!(
+            replay("# header\n0,send,0,100\n5,resend,0,100").unwrap_err(),
+            ReplayError {
+                line: 3,
+                message: "unknown event `resend`".to_string()
+            }
+        );
+        assert_eq!(replay("10,send,0,100\n5,ack,0,0").unwrap_err().line, 2);
+    }
+}
@@ -0,0 +1,298 @@
+use std::time::{Duration, Instant};
+
+use crate::{DeliveryRate, RateKind, RateSample};
+
+#[derive(Debug, Clone)]
+pub struct ResumeValidatorConfig {
+    /// How long after resuming the evidence is examined
+    pub window: Duration,
+    /// A measured rate below `prior * downgrade_ratio` contradicts the prior
+    pub downgrade_ratio: f64,
+    /// Losing this much data (measured in octets or packets) within the window contradicts the prior
+    pub max_lost: u64,
+}
+impl Default for ResumeValidatorConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_millis(500),
+            downgrade_ratio: 0.5,
+            max_lost: 3,
+        }
+    }
+}
+
+/// The outcome of validating a prior
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResumeVerdict {
+    /// Nothing contradicted the prior within the validation window
+    Confirmed {
+        /// The number of [`RateKind::Measured`] samples examined
+        measured_samples: usize,
+    },
+    /// A measured sample fell far below the prior
+    ///
+    /// The controller should fall back to the measured rate.
+    Downgraded {
+        prior: DeliveryRate,
+        measured: DeliveryRate,
+    },
+    /// Losses exceeded [`ResumeValidatorConfig::max_lost`]
+    ///
+    /// The controller should abandon the prior and restart from measured-only state.
+    Aborted {
+        /// The amount of data lost within the window
+        lost: u64,
+    },
+}
+
+/// Careful-resume style validation of a connection that jumped ahead on a prior (e.g. a [`crate::destination_cache::CachedEstimate`])
+///
+/// Once a verdict is reached, it is final.
+#[derive(Debug, Clone)]
+pub struct ResumeValidator {
+    config: ResumeValidatorConfig,
+    prior: DeliveryRate,
+    deadline: Instant,
+    lost: u64,
+    measured_samples: usize,
+    verdict: Option<ResumeVerdict>,
+}
+impl ResumeValidator {
+    pub fn new(config: ResumeValidatorConfig, prior: DeliveryRate, now: Instant) -> Self {
+        let deadline = now + config.window;
+        Self {
+            config,
+            prior,
+            deadline,
+            lost: 0,
+            measured_samples: 0,
+            verdict: None,
+        }
+    }
+
+    /// Upon a rate sample being produced
+    ///
+    /// - [`RateKind::LowerBound`] samples can confirm but never downgrade the prior.
+    /// - Unreliable samples are ignored.
+    pub fn on_sample(&mut self, sample: &RateSample, now: Instant) -> Option<ResumeVerdict> {
+        if self.verdict.is_some() || self.expire(now) {
+            return self.verdict;
+        }
+        if sample.rate_kind() == RateKind::LowerBound || !sample.is_reliable() {
+            return None;
+        }
+        self.measured_samples += 1;
+        let measured = sample.delivery_rate();
+        if measured < self.prior * self.config.downgrade_ratio {
+            self.verdict = Some(ResumeVerdict::Downgraded {
+                prior: self.prior,
+                measured,
+            });
+        }
+        self.verdict
+    }
+
+    /// Upon data being marked as lost
+    ///
+    /// `lost`: measured in octets or packets
+    pub fn on_loss(&mut self, lost: u64, now: Instant) -> Option<ResumeVerdict> {
+        if self.verdict.is_some() || self.expire(now) {
+            return self.verdict;
+        }
+        self.lost += lost;
+        if self.config.max_lost <= self.lost {
+            self.verdict = Some(ResumeVerdict::Aborted { lost: self.lost });
+        }
+        self.verdict
+    }
+
+    /// Confirm the prior if the window has passed without contradiction
+    pub fn poll(&mut self, now: Instant) -> Option<ResumeVerdict> {
+        if self.verdict.is_none() {
+            self.expire(now);
+        }
+        self.verdict
+    }
+
+    pub fn verdict(&self) -> Option<ResumeVerdict> {
+        self.verdict
+    }
+
+    pub fn prior(&self) -> DeliveryRate {
+        self.prior
+    }
+
+    fn expire(&mut self, now: Instant) -> bool {
+        if now < self.deadline {
+            return false;
+        }
+        self.verdict = Some(ResumeVerdict::Confirmed {
+            measured_samples: self.measured_samples,
+        });
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+    use crate::{ConnectionState, Packet};
+
+    fn sample(delivery_rate: f64, is_app_limited: bool, now: Instant) -> RateSample {
+        RateSample::synthetic(
+            delivery_rate,
+            is_app_limited,
+            now,
+            Duration::from_millis(100),
+        )
+    }
+
+    fn validator(now: Instant) -> ResumeValidator {
+        let prior = DeliveryRate::from_bytes_per_sec(1_000.);
+        ResumeValidator::new(ResumeValidatorConfig::default(), prior, now)
+    }
+
+    #[test]
+    fn test_confirm() {
+        let now = Instant::now();
+        let mut v = validator(now);
+        let ms = Duration::from_millis(1);
+        assert_eq!(v.on_sample(&sample(900., false, now), now + 100 * ms), None);
+        // App-limited samples are only lower bounds
+        assert_eq!(v.on_sample(&sample(10., true, now), now + 200 * ms), None);
+        assert_eq!(v.on_loss(1, now + 300 * ms), None);
+        assert_eq!(
+            v.poll(now + 500 * ms),
+            Some(ResumeVerdict::Confirmed {
+                measured_samples: 1
+            })
+        );
+        // Final
+        assert_eq!(v.on_loss(10, now + 600 * ms), v.verdict());
+    }
+
+    #[test]
+    fn test_downgrade() {
+        let now = Instant::now();
+        let mut v = validator(now);
+        let t = now + Duration::from_millis(100);
+        assert_eq!(v.on_sample(&sample(600., false, now), t), None);
+        assert_eq!(
+            v.on_sample(&sample(400., false, now), t),
+            Some(ResumeVerdict::Downgraded {
+                prior: DeliveryRate::from_bytes_per_sec(1_000.),
+                measured: DeliveryRate::from_bytes_per_sec(400.),
+            })
+        );
+    }
+
+    #[test]
+    fn test_abort() {
+        let now = Instant::now();
+        let mut v = validator(now);
+        let t = now + Duration::from_millis(100);
+        assert_eq!(v.on_loss(2, t), None);
+        assert_eq!(v.on_loss(1, t), Some(ResumeVerdict::Aborted { lost: 3 }));
+        assert_eq!(
+            v.on_sample(&sample(1_000., false, now), t),
+            Some(ResumeVerdict::Aborted { lost: 3 })
+        );
+    }
+
+    /// A sender jumping ahead to pace at a prior of 1_000_000 octets per second over a bottleneck of `link` octets per second,
+    /// a 50 ms RTT, and a buffer of `buffer` packets, until the validator reaches a verdict
+    ///
+    /// A packet dropped at the bottleneck is declared lost one RTT after it was sent.
+    fn resume(link: f64, buffer: usize) -> ResumeVerdict {
+        let start = Instant::now();
+        let prior = 1_000_000.;
+        let mss = 1_000;
+        let rtt = Duration::from_millis(50);
+        let gap = Duration::from_secs_f64(mss as f64 / prior);
+        let serialization = Duration::from_secs_f64(mss as f64 / link);
+        let config = ResumeValidatorConfig {
+            max_lost: 3 * mss,
+            ..Default::default()
+        };
+        let prior = DeliveryRate::from_bytes_per_sec(prior);
+        let mut v = ResumeValidator::new(config, prior, start);
+        let mut c = ConnectionState::new(start);
+        // Departure times from the bottleneck
+        let mut queue: VecDeque<Instant> = VecDeque::new();
+        let mut acks = VecDeque::new();
+        let mut losses = VecDeque::new();
+        let mut next_send = start;
+        loop {
+            let next_ack = acks.front().map(|(t, _)| *t);
+            let next_loss = losses.front().copied();
+            let now = [Some(next_send), next_ack, next_loss]
+                .into_iter()
+                .flatten()
+                .min()
+                .unwrap();
+            if let Some(verdict) = v.poll(now) {
+                return verdict;
+            }
+            let verdict = if next_loss == Some(now) {
+                losses.pop_front();
+                c.on_loss(mss);
+                v.on_loss(mss, now)
+            } else if next_ack == Some(now) {
+                let (_, state) = acks.pop_front().unwrap();
+                let packet = Packet {
+                    state,
+                    data_length: mss,
+                };
+                c.sample_rate(&[packet], now, Duration::ZERO)
+                    .and_then(|rs| v.on_sample(&rs, now))
+            } else {
+                let state = c.send_packet_2(now, now == start);
+                while queue.front().is_some_and(|t| *t <= now) {
+                    queue.pop_front();
+                }
+                if queue.len() < buffer {
+                    let departure = queue.back().copied().unwrap_or(now).max(now) + serialization;
+                    queue.push_back(departure);
+                    acks.push_back((departure + rtt, state));
+                } else {
+                    losses.push_back(now + rtt);
+                }
+                next_send += gap;
+                None
+            };
+            if let Some(verdict) = verdict {
+                return verdict;
+            }
+        }
+    }
+
+    #[test]
+    fn test_resume_confirmed() {
+        // The path is as fast as remembered
+        let verdict = resume(1_000_000., 1_000);
+        let ResumeVerdict::Confirmed { measured_samples } = verdict else {
+            panic!("{verdict:?}");
+        };
+        assert!(100 < measured_samples, "{measured_samples}");
+    }
+
+    #[test]
+    fn test_resume_downgraded() {
+        // A quarter of the remembered rate, with a buffer deep enough to absorb the overshoot
+        let verdict = resume(250_000., 1_000);
+        let ResumeVerdict::Downgraded { measured, .. } = verdict else {
+            panic!("{verdict:?}");
+        };
+        let measured = measured.as_bytes_per_sec();
+        assert!((measured - 250_000.).abs() < 25_000., "{measured}");
+    }
+
+    #[test]
+    fn test_resume_aborted() {
+        // A quarter of the remembered rate, with a shallow buffer overflowing before any sample
+        let verdict = resume(250_000., 10);
+        assert_eq!(verdict, ResumeVerdict::Aborted { lost: 3_000 });
+    }
+}
@@ -0,0 +1,236 @@
+//! s2n-quic's `CongestionController`, with [`ConnectionState`] as the measurement core
+//!
+//! s2n-quic reports an `ACK` as one aggregate, the info of the newest acknowledged packet plus the total bytes acknowledged, which makes up exactly one rate sample.
+//! Plug [`S2nQuicEndpoint`] in as s2n-quic's congestion controller provider to run the adapter on every path.
+
+use std::time::Instant;
+
+use s2n_quic_core::{
+    random,
+    recovery::{
+        congestion_controller::{Endpoint, PathInfo, Publisher},
+        CongestionController, RttEstimator,
+    },
+    time::Timestamp,
+};
+
+use crate::{
+    estimator::{BandwidthEstimator, WindowedMaxBandwidth},
+    ConnectionState, DeliveryRate, PacketState, RateSample,
+};
+
+/// The congestion window before the first bandwidth estimate, in packets
+const INITIAL_WINDOW_PACKETS: u32 = 10;
+/// The congestion window never shrinks below this many packets
+const MIN_WINDOW_PACKETS: u32 = 4;
+/// The congestion window is this many bandwidth-delay products
+const CWND_GAIN: f64 = 2.;
+/// The bandwidth is the max over this many round trips
+const BW_WINDOW_ROUNDS: u64 = 10;
+
+/// s2n-quic's byte counts, saturating at the range of its `bytes_in_flight`
+fn saturate(bytes: usize) -> u32 {
+    u32::try_from(bytes).unwrap_or(u32::MAX)
+}
+
+/// s2n-quic's congestion controller provider, creating an [`S2nQuicAdapter`] per path
+#[derive(Debug, Default)]
+pub struct S2nQuicEndpoint {}
+impl Endpoint for S2nQuicEndpoint {
+    type CongestionController = S2nQuicAdapter;
+
+    fn new_congestion_controller(&mut self, path_info: PathInfo) -> Self::CongestionController {
+        S2nQuicAdapter::new(path_info.max_datagram_size)
+    }
+}
+
+/// Delivery rate sampling and a rate-based congestion window for s2n-quic
+///
+/// The congestion window is [`CWND_GAIN`] bandwidth-delay products of the windowed max bandwidth and the min RTT.
+/// Data is measured in octets.
+#[derive(Debug, Clone)]
+pub struct S2nQuicAdapter {
+    /// The [`Instant`] of [`Self::anchor`]
+    origin: Instant,
+    /// The first s2n-quic [`Timestamp`] seen; later ones are converted to [`Instant`]s relative to it
+    anchor: Option<Timestamp>,
+    state: ConnectionState,
+    bandwidth: WindowedMaxBandwidth,
+    max_datagram_size: u16,
+    bytes_in_flight: u32,
+    congestion_window: u32,
+    latest: Option<RateSample>,
+}
+impl S2nQuicAdapter {
+    /// `max_datagram_size`: the initial max UDP payload, as s2n-quic's `PathInfo` reports it
+    pub fn new(max_datagram_size: u16) -> Self {
+        let origin = Instant::now();
+        Self {
+            origin,
+            anchor: None,
+            state: ConnectionState::new(origin),
+            bandwidth: WindowedMaxBandwidth::new(BW_WINDOW_ROUNDS),
+            max_datagram_size,
+            bytes_in_flight: 0,
+            congestion_window: INITIAL_WINDOW_PACKETS * u32::from(max_datagram_size),
+            latest: None,
+        }
+    }
+
+    fn instant(&mut self, timestamp: Timestamp) -> Instant {
+        let anchor = *self.anchor.get_or_insert(timestamp);
+        if anchor <= timestamp {
+            self.origin + timestamp.saturating_duration_since(anchor)
+        } else {
+            let before = anchor.saturating_duration_since(timestamp);
+            self.origin.checked_sub(before).unwrap_or(self.origin)
+        }
+    }
+
+    fn update_congestion_window(&mut self) {
+        let min_window = MIN_WINDOW_PACKETS * u32::from(self.max_datagram_size);
+        let (Some(bw), Some(min_rtt)) = (self.bandwidth.bandwidth(), self.state.min_rtt()) else {
+            self.congestion_window = self.congestion_window.max(min_window);
+            return;
+        };
+        let bdp = bw.as_bytes_per_sec() * min_rtt.as_secs_f64();
+        let congestion_window = (CWND_GAIN * bdp).clamp(0., f64::from(u32::MAX)) as u32;
+        self.congestion_window = congestion_window.max(min_window);
+    }
+
+    /// The windowed max of the measured delivery rate
+    pub fn bandwidth(&self) -> Option<DeliveryRate> {
+        self.bandwidth.bandwidth()
+    }
+
+    /// The most recent rate sample
+    pub fn latest_sample(&self) -> Option<&RateSample> {
+        self.latest.as_ref()
+    }
+
+    pub fn connection_state(&self) -> &ConnectionState {
+        &self.state
+    }
+}
+impl CongestionController for S2nQuicAdapter {
+    type PacketInfo = PacketState;
+
+    fn congestion_window(&self) -> u32 {
+        self.congestion_window
+    }
+
+    fn bytes_in_flight(&self) -> u32 {
+        self.bytes_in_flight
+    }
+
+    fn is_congestion_limited(&self) -> bool {
+        let available = self.congestion_window.saturating_sub(self.bytes_in_flight);
+        available < u32::from(self.max_datagram_size)
+    }
+
+    fn requires_fast_retransmission(&self) -> bool {
+        false
+    }
+
+    /// `app_limited`: [`Some`] with `true` if the application had no more data to send
+    fn on_packet_sent<Pub: Publisher>(
+        &mut self,
+        time_sent: Timestamp,
+        sent_bytes: usize,
+        app_limited: Option<bool>,
+        _rtt_estimator: &RttEstimator,
+        _publisher: &mut Pub,
+    ) -> PacketState {
+        if app_limited == Some(true) {
+            self.state
+                .set_application_limited_phases(u64::from(self.bytes_in_flight));
+        }
+        let time_sent = self.instant(time_sent);
+        let info = self
+            .state
+            .send_packet_2(time_sent, self.bytes_in_flight == 0);
+        self.bytes_in_flight = self.bytes_in_flight.saturating_add(saturate(sent_bytes));
+        info
+    }
+
+    fn on_rtt_update<Pub: Publisher>(
+        &mut self,
+        _time_sent: Timestamp,
+        _now: Timestamp,
+        rtt_estimator: &RttEstimator,
+        _publisher: &mut Pub,
+    ) {
+        self.state.on_rtt_sample(rtt_estimator.latest_rtt());
+    }
+
+    fn on_ack<Pub: Publisher>(
+        &mut self,
+        _newest_acked_time_sent: Timestamp,
+        bytes_acknowledged: usize,
+        newest_acked_packet_info: PacketState,
+        _rtt_estimator: &RttEstimator,
+        _random_generator: &mut dyn random::Generator,
+        ack_receive_time: Timestamp,
+        _publisher: &mut Pub,
+    ) {
+        self.bytes_in_flight = self
+            .bytes_in_flight
+            .saturating_sub(saturate(bytes_acknowledged));
+        let now = self.instant(ack_receive_time);
+        let min_rtt = self.state.min_rtt().unwrap_or_default();
+        let Some(sample) = self.state.sample_rate_iter(
+            [(newest_acked_packet_info, bytes_acknowledged as u64)],
+            now,
+            min_rtt,
+        ) else {
+            return;
+        };
+        self.bandwidth.on_sample(&sample);
+        self.update_congestion_window();
+        self.latest = Some(sample);
+    }
+
+    /// Lost data leaves the flight without being delivered; a new loss burst starts a new epoch
+    fn on_packet_lost<Pub: Publisher>(
+        &mut self,
+        lost_bytes: u32,
+        _packet_info: PacketState,
+        _persistent_congestion: bool,
+        new_loss_burst: bool,
+        _random_generator: &mut dyn random::Generator,
+        _timestamp: Timestamp,
+        _publisher: &mut Pub,
+    ) {
+        self.bytes_in_flight = self.bytes_in_flight.saturating_sub(lost_bytes);
+        self.state.on_loss(u64::from(lost_bytes));
+        if new_loss_burst {
+            self.state.begin_epoch();
+        }
+    }
+
+    /// A congestion event like a new loss burst
+    fn on_explicit_congestion<Pub: Publisher>(
+        &mut self,
+        ce_count: u64,
+        _event_time: Timestamp,
+        _publisher: &mut Pub,
+    ) {
+        if ce_count != 0 {
+            self.state.begin_epoch();
+        }
+    }
+
+    fn on_mtu_update<Pub: Publisher>(&mut self, max_data_size: u16, _publisher: &mut Pub) {
+        self.max_datagram_size = max_data_size;
+        self.update_congestion_window();
+    }
+
+    fn on_packet_discarded<Pub: Publisher>(&mut self, bytes_sent: usize, _publisher: &mut Pub) {
+        self.bytes_in_flight = self.bytes_in_flight.saturating_sub(saturate(bytes_sent));
+    }
+
+    /// No pacing: packets leave as the congestion window allows
+    fn earliest_departure_time(&self) -> Option<Timestamp> {
+        None
+    }
+}
@@ -0,0 +1,227 @@
+//! A bounded, runtime-agnostic channel handing rate samples from `ACK` processing to async consumers
+//!
+//! [`RateSampleStream`] is a [`futures_core::Stream`]; it depends on no async runtime.
+//!
+//! # Backpressure
+//!
+//! The sender never blocks or waits: `ACK` processing must not stall on a slow consumer.
+//! A sample sent at capacity evicts the oldest queued one and counts it as dropped.
+//!
+//! # Wakeups
+//!
+//! The consumer's waker is registered by a pending poll and woken once by the next [`SampleSender::send()`] or by the sender being dropped, which ends the stream once the queue drains.
+
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use crate::RateSample;
+
+#[derive(Debug)]
+struct Shared {
+    queue: VecDeque<RateSample>,
+    capacity: usize,
+    dropped: u64,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+/// Create a channel holding at most `capacity` samples
+///
+/// # Panics
+///
+/// If `capacity` is zero
+pub fn channel(capacity: usize) -> (SampleSender, RateSampleStream) {
+    assert!(0 < capacity, "zero capacity");
+    let shared = Arc::new(Mutex::new(Shared {
+        queue: VecDeque::with_capacity(capacity),
+        capacity,
+        dropped: 0,
+        waker: None,
+        closed: false,
+    }));
+    (
+        SampleSender {
+            shared: Arc::clone(&shared),
+        },
+        RateSampleStream { shared },
+    )
+}
+
+/// The handle `ACK` processing passes samples into
+#[derive(Debug)]
+pub struct SampleSender {
+    shared: Arc<Mutex<Shared>>,
+}
+impl SampleSender {
+    /// Never waits; evicts the oldest queued sample at capacity
+    pub fn send(&self, sample: RateSample) {
+        let waker = {
+            let mut shared = self.shared.lock().unwrap();
+            if shared.queue.len() == shared.capacity {
+                shared.queue.pop_front();
+                shared.dropped += 1;
+            }
+            shared.queue.push_back(sample);
+            shared.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// The number of samples evicted unread
+    pub fn dropped(&self) -> u64 {
+        self.shared.lock().unwrap().dropped
+    }
+}
+impl Drop for SampleSender {
+    fn drop(&mut self) {
+        let waker = {
+            let mut shared = self.shared.lock().unwrap();
+            shared.closed = true;
+            shared.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// The consuming end, yielding samples oldest first
+#[derive(Debug)]
+pub struct RateSampleStream {
+    shared: Arc<Mutex<Shared>>,
+}
+impl RateSampleStream {
+    /// [`Poll::Ready`] with [`None`] once the sender is dropped and the queue is drained
+    pub fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<RateSample>> {
+        let mut shared = self.shared.lock().unwrap();
+        if let Some(sample) = shared.queue.pop_front() {
+            return Poll::Ready(Some(sample));
+        }
+        if shared.closed {
+            return Poll::Ready(None);
+        }
+        shared.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    /// Wait for the next sample
+    pub fn recv(&mut self) -> Recv<'_> {
+        Recv { stream: self }
+    }
+
+    /// The number of samples evicted unread
+    pub fn dropped(&self) -> u64 {
+        self.shared.lock().unwrap().dropped
+    }
+}
+
+impl futures_core::Stream for RateSampleStream {
+    type Item = RateSample;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        RateSampleStream::poll_next(self, cx)
+    }
+}
+
+/// The future returned by [`RateSampleStream::recv()`]
+#[derive(Debug)]
+pub struct Recv<'a> {
+    stream: &'a mut RateSampleStream,
+}
+impl Future for Recv<'_> {
+    type Output = Option<RateSample>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut *self.stream).poll_next(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        task::Wake,
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn sample(rate: f64) -> RateSample {
+        RateSample::synthetic(rate, false, Instant::now(), Duration::from_secs(1))
+    }
+
+    fn poll(stream: &mut RateSampleStream, waker: &Arc<CountingWaker>) -> Poll<Option<f64>> {
+        let waker = Waker::from(Arc::clone(waker));
+        let mut cx = Context::from_waker(&waker);
+        let mut recv = stream.recv();
+        Pin::new(&mut recv)
+            .poll(&mut cx)
+            .map(|rs| rs.map(|rs| rs.delivery_rate_raw()))
+    }
+
+    #[test]
+    fn test_drop_oldest() {
+        let (sender, mut stream) = channel(2);
+        let waker = Arc::new(CountingWaker::default());
+        for rate in 1..=3 {
+            sender.send(sample(rate as f64));
+        }
+        assert_eq!(sender.dropped(), 1);
+        assert_eq!(poll(&mut stream, &waker), Poll::Ready(Some(2.)));
+        assert_eq!(poll(&mut stream, &waker), Poll::Ready(Some(3.)));
+        assert_eq!(stream.dropped(), 1);
+    }
+
+    #[test]
+    fn test_stream() {
+        use futures_core::Stream;
+
+        fn next<S: Stream + Unpin>(stream: &mut S, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+            Stream::poll_next(Pin::new(stream), cx)
+        }
+        let (sender, mut stream) = channel(2);
+        let waker = Waker::from(Arc::new(CountingWaker::default()));
+        let mut cx = Context::from_waker(&waker);
+        sender.send(sample(1.));
+        let rs = next(&mut stream, &mut cx).map(|rs| rs.map(|rs| rs.delivery_rate_raw()));
+        assert_eq!(rs, Poll::Ready(Some(1.)));
+        assert!(next(&mut stream, &mut cx).is_pending());
+        drop(sender);
+        assert!(matches!(next(&mut stream, &mut cx), Poll::Ready(None)));
+    }
+
+    #[test]
+    fn test_wakeups() {
+        let (sender, mut stream) = channel(4);
+        let waker = Arc::new(CountingWaker::default());
+        assert_eq!(poll(&mut stream, &waker), Poll::Pending);
+        assert_eq!(waker.0.load(Ordering::SeqCst), 0);
+
+        sender.send(sample(1.));
+        // Woken once, not per sample
+        sender.send(sample(2.));
+        assert_eq!(waker.0.load(Ordering::SeqCst), 1);
+        assert_eq!(poll(&mut stream, &waker), Poll::Ready(Some(1.)));
+        assert_eq!(poll(&mut stream, &waker), Poll::Ready(Some(2.)));
+
+        assert_eq!(poll(&mut stream, &waker), Poll::Pending);
+        drop(sender);
+        assert_eq!(waker.0.load(Ordering::SeqCst), 2);
+        assert_eq!(poll(&mut stream, &waker), Poll::Ready(None));
+    }
+}
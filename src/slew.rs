@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use crate::DeliveryRate;
+
+/// A pacing rate that follows a target at a bounded rate of change
+///
+/// Sits between the bandwidth estimate and the pacer, so an abrupt change of the estimate does not turn into a burst.
+#[derive(Debug, Clone)]
+pub struct SlewLimitedRate {
+    /// The largest change of the rate per second, measured in octets (or packets) per second per second
+    max_slew: f64,
+    current: Option<DeliveryRate>,
+}
+impl SlewLimitedRate {
+    /// `max_slew`: the largest change of the rate per second, measured in octets (or packets) per second per second;
+    /// e.g. `1.25e6` lets the rate move by 10 Mbps each second
+    ///
+    /// Panics if `max_slew` is negative, infinite, or NaN.
+    pub fn new(max_slew: f64) -> Self {
+        assert!(
+            max_slew.is_finite() && 0. <= max_slew,
+            "max_slew {max_slew} is not a finite non-negative number"
+        );
+        Self {
+            max_slew,
+            current: None,
+        }
+    }
+
+    /// Move toward `target` by at most `max_slew * elapsed`
+    ///
+    /// The first target is taken as is, since there is no rate to move from.
+    /// `elapsed`: the time since the previous update
+    pub fn update(&mut self, target: DeliveryRate, elapsed: Duration) -> DeliveryRate {
+        let Some(current) = self.current else {
+            self.current = Some(target);
+            return target;
+        };
+        let current = current.as_bytes_per_sec();
+        let step = self.max_slew * elapsed.as_secs_f64();
+        let next = target
+            .as_bytes_per_sec()
+            .clamp(current - step, current + step);
+        let next = DeliveryRate::from_bytes_per_sec(next);
+        self.current = Some(next);
+        next
+    }
+
+    pub fn rate(&self) -> Option<DeliveryRate> {
+        self.current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_approached_gradually() {
+        let ms = Duration::from_millis(1);
+        let rate = DeliveryRate::from_bytes_per_sec;
+        let mut pacing = SlewLimitedRate::new(1_000.);
+        assert_eq!(pacing.update(rate(1_000.), ms), rate(1_000.));
+
+        // Step up to 2_000: 100 per 100 ms
+        let mut rates = vec![];
+        for _ in 0..12 {
+            rates.push(
+                pacing
+                    .update(rate(2_000.), 100 * ms)
+                    .as_bytes_per_sec()
+                    .round(),
+            );
+        }
+        assert_eq!(
+            rates,
+            [
+                1_100., 1_200., 1_300., 1_400., 1_500., 1_600., 1_700., 1_800., 1_900., 2_000.,
+                2_000., 2_000.
+            ]
+        );
+
+        // Step down
+        assert_eq!(pacing.update(rate(0.), 500 * ms), rate(1_500.));
+        assert_eq!(pacing.rate(), Some(rate(1_500.)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_negative_slew() {
+        SlewLimitedRate::new(-1.);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_nan_slew() {
+        SlewLimitedRate::new(f64::NAN);
+    }
+}
@@ -0,0 +1,128 @@
+use std::time::{Duration, Instant};
+
+use crate::{ConnectionState, DeliveryRate, RateSample};
+
+/// Lifetime aggregates of a connection, e.g. for logging at close time
+///
+/// Accumulated passively by [`ConnectionState`] with a few counter updates per `ACK`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ConnectionStats {
+    /// Measured in octets or packets
+    pub delivered: u64,
+    /// Measured in octets or packets, as reported to [`ConnectionState::on_loss()`]
+    pub lost: u64,
+    /// The largest reliable delivery rate sampled, and when it was sampled
+    pub peak_delivery_rate: Option<(DeliveryRate, Instant)>,
+    /// The number of `ACK`s that produced a rate sample
+    pub samples_generated: u64,
+    /// The number of `ACK`s that delivered data but produced no rate sample
+    pub samples_rejected: u64,
+    /// The time spent application-limited, at `ACK` granularity
+    ///
+    /// The time between two `ACK`s counts if the connection was application-limited when the later one arrived.
+    pub app_limited_time: Duration,
+    /// The number of times sending restarted from idle
+    pub idle_restarts: u64,
+    /// The number of [`crate::PacketClass::WindowProbe`] packets acknowledged
+    pub window_probes_acked: u64,
+    /// The number of packets listed again within the same `ACK` and not counted twice
+    pub duplicate_packets_ignored: u64,
+}
+impl ConnectionStats {
+    /// `app_limited_since`: the previous `ACK` time if the connection was application-limited before this `ACK`
+    pub(crate) fn on_ack(
+        &mut self,
+        delivered: u64,
+        sample: Option<&RateSample>,
+        app_limited_since: Option<Instant>,
+        now: Instant,
+    ) {
+        self.delivered += delivered;
+        if let Some(since) = app_limited_since {
+            self.app_limited_time += now.saturating_duration_since(since);
+        }
+        let Some(sample) = sample else {
+            if delivered != 0 {
+                self.samples_rejected += 1;
+            }
+            return;
+        };
+        self.samples_generated += 1;
+        if !sample.is_reliable() {
+            return;
+        }
+        let rate = sample.delivery_rate();
+        if self.peak_delivery_rate.is_none_or(|(peak, _)| peak < rate) {
+            self.peak_delivery_rate = Some((rate, now));
+        }
+    }
+}
+
+impl ConnectionState {
+    pub fn stats(&self) -> ConnectionStats {
+        self.stats
+    }
+
+    /// Start accumulating [`Self::stats()`] from zero
+    pub fn reset_stats(&mut self) {
+        self.stats = ConnectionStats::default();
+    }
+
+    /// Upon data being marked as lost
+    ///
+    /// `lost`: measured in octets or packets; only counted in [`ConnectionStats::lost`]
+    pub fn on_loss(&mut self, lost: u64) {
+        self.stats.lost += lost;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Packet;
+
+    #[test]
+    fn test_scripted_connection() {
+        let now = Instant::now();
+        let s = Duration::from_secs(1);
+        let mut c = ConnectionState::new(now);
+        let ack = |c: &mut ConnectionState, state, data_length, now| {
+            c.sample_rate(&[Packet { state, data_length }], now, Duration::ZERO)
+        };
+
+        // First ACK delivers data but yields no sample
+        let p = c.send_packet_2(now, true);
+        assert!(ack(&mut c, p, 10, now + s).is_none());
+
+        // 20 per second
+        let p_1 = c.send_packet_2(now + s, true);
+        let p_2 = c.send_packet_2(now + s, false);
+        let rs = ack(&mut c, p_1, 20, now + 2 * s).unwrap();
+        assert_eq!(rs.delivery_rate_raw(), 20.);
+        c.on_loss(5);
+
+        // Application-limited from here on
+        c.set_application_limited_phases(0);
+        let rs = ack(&mut c, p_2, 10, now + 4 * s).unwrap();
+        assert_eq!(rs.delivery_rate_raw(), 10.);
+
+        let stats = c.stats();
+        assert_eq!(
+            stats,
+            ConnectionStats {
+                delivered: 40,
+                lost: 5,
+                peak_delivery_rate: Some((DeliveryRate::from_bytes_per_sec(20.), now + 2 * s)),
+                samples_generated: 2,
+                samples_rejected: 1,
+                app_limited_time: 2 * s,
+                idle_restarts: 2,
+                window_probes_acked: 0,
+                duplicate_packets_ignored: 0,
+            }
+        );
+
+        c.reset_stats();
+        assert_eq!(c.stats(), ConnectionStats::default());
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::{throughput::RecentThroughput, DeliveryRate};
+
+/// Per-stream delivery of a stream
+#[derive(Debug, Clone)]
+struct StreamDelivery {
+    delivered: u64,
+    throughput: RecentThroughput,
+}
+
+/// Per-stream goodput on a multiplexed connection, layered beside the connection-wide [`crate::ConnectionState`]
+///
+/// Each packet records how much data each stream contributed; when the packet is acknowledged, its data is credited back to those streams.
+///
+/// Data is measured in octets.
+#[derive(Debug, Clone)]
+pub struct StreamAccountant {
+    window: Duration,
+    /// Packet number to `(stream ID, data length)` contributions
+    in_flight: HashMap<u64, Vec<(u64, u64)>>,
+    streams: HashMap<u64, StreamDelivery>,
+}
+impl StreamAccountant {
+    /// `window`: the span [`Self::rate()`] averages over
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            in_flight: HashMap::new(),
+            streams: HashMap::new(),
+        }
+    }
+
+    /// `contributions`: `(stream ID, data length)` of each stream's data in the packet
+    pub fn on_packet_sent(&mut self, pkt_num: u64, contributions: &[(u64, u64)]) {
+        self.in_flight
+            .entry(pkt_num)
+            .or_default()
+            .extend_from_slice(contributions);
+    }
+
+    /// Upon the stream data of `old_pkt_num` being retransmitted in `new_pkt_num`
+    ///
+    /// The data moves to the new packet, so it is credited once whichever of the two is acknowledged.
+    /// Call it before or after [`Self::on_packet_sent()`] of the new packet, which may carry new data besides.
+    pub fn on_packet_retransmitted(&mut self, old_pkt_num: u64, new_pkt_num: u64) {
+        let Some(contributions) = self.in_flight.remove(&old_pkt_num) else {
+            return;
+        };
+        self.in_flight
+            .entry(new_pkt_num)
+            .or_default()
+            .extend(contributions);
+    }
+
+    /// Credit the packet's data to its streams; unknown packets (e.g. already credited) are ignored
+    pub fn on_packet_acked(&mut self, pkt_num: u64, now: Instant) {
+        let Some(contributions) = self.in_flight.remove(&pkt_num) else {
+            return;
+        };
+        for (stream, data_length) in contributions {
+            let window = self.window;
+            let stream = self
+                .streams
+                .entry(stream)
+                .or_insert_with(|| StreamDelivery {
+                    delivered: 0,
+                    throughput: RecentThroughput::new(window),
+                });
+            stream.delivered += data_length;
+            stream.throughput.on_ack(now, data_length);
+        }
+    }
+
+    /// Forget the packet; its data is expected to be retransmitted via [`Self::on_packet_retransmitted()`] beforehand if at all
+    pub fn on_packet_lost(&mut self, pkt_num: u64) {
+        self.in_flight.remove(&pkt_num);
+    }
+
+    /// The stream data delivered so far
+    pub fn delivered(&self, stream: u64) -> u64 {
+        self.streams.get(&stream).map_or(0, |s| s.delivered)
+    }
+
+    /// The stream's goodput averaged over the window ending at `now`; [`None`] if nothing of the stream was delivered
+    pub fn rate(&self, stream: u64, now: Instant) -> Option<DeliveryRate> {
+        self.streams
+            .get(&stream)?
+            .throughput
+            .rate_over(self.window, now)
+    }
+
+    /// Stop tracking a closed stream
+    pub fn remove_stream(&mut self, stream: u64) {
+        self.streams.remove(&stream);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ratio_recovered() {
+        let start = Instant::now();
+        let ms = Duration::from_millis(1);
+        let mut accountant = StreamAccountant::new(200 * ms);
+        // Stream 1 fills whole packets; every other packet also carries stream 2
+        let mut next_pkt_num = 0;
+        for k in 0..100 {
+            let now = start + k * 10 * ms;
+            let contributions: &[(u64, u64)] = if k % 2 == 0 {
+                &[(1, 1_000)]
+            } else {
+                &[(1, 500), (2, 500)]
+            };
+            accountant.on_packet_sent(next_pkt_num, contributions);
+            if k % 10 == 3 {
+                // Lost and retransmitted; the original shows up late too
+                accountant.on_packet_retransmitted(next_pkt_num, next_pkt_num + 1);
+                accountant.on_packet_acked(next_pkt_num + 1, now);
+                accountant.on_packet_acked(next_pkt_num, now);
+            } else {
+                accountant.on_packet_acked(next_pkt_num, now);
+            }
+            next_pkt_num += 2;
+        }
+        assert_eq!(accountant.delivered(1), 75_000);
+        assert_eq!(accountant.delivered(2), 25_000);
+        let now = start + 990 * ms;
+        let rate_1 = accountant.rate(1, now).unwrap().as_bytes_per_sec();
+        let rate_2 = accountant.rate(2, now).unwrap().as_bytes_per_sec();
+        assert_eq!(rate_1 / rate_2, 3.);
+        assert_eq!(accountant.rate(3, now), None);
+    }
+}
@@ -0,0 +1,301 @@
+//! The per-packet pass of [`ConnectionState::sample_rate_iter()`](crate::ConnectionState::sample_rate_iter), serial or, with the `rayon` feature, a parallel reduction
+
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+use crate::{Overhead, PacketClass, PacketState, SamplingStrategy};
+
+/// Identifies the data of an acknowledged packet, so that it is counted once per `ACK`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct AckKey {
+    transmission: Option<u64>,
+    id: Option<u64>,
+    slice: u64,
+}
+impl AckKey {
+    /// [`None`] if the state can not be told apart from others
+    fn of(state: &PacketState) -> Option<Self> {
+        if state.transmission.is_none() && state.id.is_none() {
+            return None;
+        }
+        Some(Self {
+            transmission: state.transmission,
+            id: state.id,
+            slice: state.slice,
+        })
+    }
+}
+
+/// What the pass reads of the connection
+#[derive(Clone, Copy)]
+pub(crate) struct TallyContext<'a> {
+    pub strategy: &'a dyn SamplingStrategy,
+    pub overhead: Overhead,
+    pub next_round_delivered: u64,
+    pub idle_epoch: u64,
+}
+
+/// The packets of one `ACK`, or a contiguous run of them
+#[derive(Debug, Default)]
+pub(crate) struct Tally {
+    pub acked: u64,
+    pub acked_source: u64,
+    pub acked_adjusted: u64,
+    pub newest_packet: Option<PacketState>,
+    /// The index of [`Self::newest_packet`] within the `ACK`
+    pub newest_index: Option<usize>,
+    pub round_end: bool,
+    pub round_app_limited: bool,
+    pub round_retransmit: bool,
+    pub has_retransmit: bool,
+    /// The send time of the first and the last packet counted
+    pub sent_time_range: Option<(Instant, Instant)>,
+    pub max_send_gap: Option<Duration>,
+    pub highest_id: Option<u64>,
+    pub acked_packet_count: u64,
+    pub window_probes: u64,
+    pub duplicates: u64,
+}
+impl Tally {
+    /// Counts the packet at `index` unless it is already in `acked_ids`
+    pub fn add(
+        &mut self,
+        index: usize,
+        state: &PacketState,
+        data_length: u64,
+        acked_ids: &mut HashSet<AckKey>,
+        cx: TallyContext<'_>,
+    ) {
+        if let Some(key) = AckKey::of(state) {
+            if !acked_ids.insert(key) {
+                self.duplicates += 1;
+                return;
+            }
+        }
+        let last = self.sent_time_range.map(|(_, last)| last);
+        self.extend_sent_time_range(state.sent_time, state.sent_time);
+        if let Some(last) = last {
+            self.on_send_gap(last, state.sent_time);
+        }
+        self.highest_id = self.highest_id.max(state.id);
+        self.has_retransmit |= state.is_retransmit;
+        self.acked_packet_count += 1;
+        self.acked += data_length;
+        if state.class != PacketClass::Repair {
+            self.acked_source += data_length;
+        }
+        self.acked_adjusted += cx.overhead.adjust(data_length);
+        if state.class == PacketClass::WindowProbe {
+            self.window_probes += 1;
+            return;
+        }
+        self.round_end |= cx.next_round_delivered <= state.delivered;
+        self.round_app_limited |= state.is_app_limited;
+        self.round_retransmit |= state.is_retransmit;
+        // Snapshots taken before the idle restart straddle the idle period
+        if state.idle_epoch < cx.idle_epoch {
+            return;
+        }
+        if cx.strategy.is_newer(state, self.newest_packet.as_ref()) {
+            self.newest_packet = Some(*state);
+            self.newest_index = Some(index);
+        }
+    }
+
+    fn extend_sent_time_range(&mut self, first: Instant, last: Instant) {
+        let first = self.sent_time_range.map_or(first, |(first, _)| first);
+        self.sent_time_range = Some((first, last));
+    }
+
+    fn on_send_gap(&mut self, a: Instant, b: Instant) {
+        let gap = b
+            .saturating_duration_since(a)
+            .max(a.saturating_duration_since(b));
+        self.max_send_gap = Some(self.max_send_gap.map_or(gap, |max| max.max(gap)));
+    }
+
+    /// Appends the tally of the packets right after those of `self`, as if one tally had counted them all
+    #[cfg(feature = "rayon")]
+    fn merge(&mut self, next: Tally, cx: TallyContext<'_>) {
+        if let Some((first, last)) = next.sent_time_range {
+            if let Some((_, previous)) = self.sent_time_range {
+                self.on_send_gap(previous, first);
+            }
+            self.extend_sent_time_range(first, last);
+        }
+        if let Some(gap) = next.max_send_gap {
+            self.max_send_gap = Some(self.max_send_gap.map_or(gap, |max| max.max(gap)));
+        }
+        if let Some(newest) = next.newest_packet {
+            if cx.strategy.is_newer(&newest, self.newest_packet.as_ref()) {
+                self.newest_packet = Some(newest);
+                self.newest_index = next.newest_index;
+            }
+        }
+        self.acked += next.acked;
+        self.acked_source += next.acked_source;
+        self.acked_adjusted += next.acked_adjusted;
+        self.round_end |= next.round_end;
+        self.round_app_limited |= next.round_app_limited;
+        self.round_retransmit |= next.round_retransmit;
+        self.has_retransmit |= next.has_retransmit;
+        self.highest_id = self.highest_id.max(next.highest_id);
+        self.acked_packet_count += next.acked_packet_count;
+        self.window_probes += next.window_probes;
+        self.duplicates += next.duplicates;
+    }
+
+    /// Tallies `packets` in contiguous runs on rayon's pool, one per thread, with the same result as a serial pass
+    ///
+    /// Adjacent runs are merged in order, so the reduction is deterministic.
+    /// Returns [`None`] if a packet repeats across runs: only a serial pass knows which occurrence comes first.
+    #[cfg(feature = "rayon")]
+    pub fn parallel<P>(packets: &[P], cx: TallyContext<'_>) -> Option<Self>
+    where
+        P: crate::AckedPacket + Sync,
+    {
+        use rayon::prelude::*;
+
+        let run_length = packets.len().div_ceil(rayon::current_num_threads()).max(1);
+        let (tally, _, repeats) = packets
+            .par_chunks(run_length)
+            .enumerate()
+            .map(|(run, packets)| {
+                let mut tally = Tally::default();
+                let mut ids = HashSet::new();
+                for (i, packet) in packets.iter().enumerate() {
+                    let index = run * run_length + i;
+                    tally.add(index, packet.state(), packet.data_length(), &mut ids, cx);
+                }
+                (tally, ids, false)
+            })
+            .reduce(
+                || (Tally::default(), HashSet::new(), false),
+                |(mut tally, mut ids, repeats), (next, next_ids, next_repeats)| {
+                    let repeats =
+                        repeats || next_repeats || next_ids.iter().any(|id| ids.contains(id));
+                    tally.merge(next, cx);
+                    ids.extend(next_ids);
+                    (tally, ids, repeats)
+                },
+            );
+        (!repeats).then_some(tally)
+    }
+}
+
+#[cfg(all(test, feature = "rayon"))]
+mod tests {
+    use std::num::NonZeroUsize;
+
+    use crate::{ConnectionState, DeliveryRateConfig, Overhead, Packet, PacketClass};
+
+    use super::*;
+
+    const PACKETS: u64 = 20_000;
+
+    /// Rounds of large `ACK`s of packets of every class, out of order, with and without repeated ids
+    fn transfer(
+        config: DeliveryRateConfig,
+        now: Instant,
+        mut f: impl FnMut(&mut ConnectionState, &[Packet], Instant),
+    ) {
+        let mut c = ConnectionState::with_config(config, now);
+        let mut t = now;
+        for round in 0..4 {
+            let mut flight: Vec<Packet> = (0..PACKETS)
+                .map(|i| {
+                    let class = match i % 50 {
+                        0 => PacketClass::Repair,
+                        1 => PacketClass::WindowProbe,
+                        _ => PacketClass::Data,
+                    };
+                    let sent = t + Duration::from_micros((i * 7_919) % PACKETS);
+                    let state = c.send_packet_with_class(sent, round == 0 && i == 0, class);
+                    Packet {
+                        state,
+                        data_length: 1_000 + i % 500,
+                    }
+                    .with_id(round * PACKETS + i)
+                })
+                .collect();
+            flight.sort_by_key(|p| (p.state.id().unwrap() * 104_729) % PACKETS);
+            t += Duration::from_millis(50);
+            let (first, rest) = flight.split_at(1);
+            f(&mut c, first, t);
+            let mut rest = rest.to_vec();
+            rest.push(rest[rest.len() / 2].clone());
+            if round % 2 == 1 {
+                // Repeated across threads
+                rest.push(rest[0].clone());
+            }
+            f(&mut c, &rest, t + Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn test_parallel_equivalence() {
+        let overhead = Overhead {
+            per_segment: 28,
+            segment_size: Some(1_200),
+        };
+        let serial = DeliveryRateConfig {
+            overhead,
+            ..Default::default()
+        };
+        let parallel = DeliveryRateConfig {
+            parallel_threshold: NonZeroUsize::new(1_000),
+            ..serial.clone()
+        };
+        // More threads than the machine may have, so that runs are split
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(3)
+            .build()
+            .unwrap();
+        let mut tallies = vec![];
+        transfer(serial.clone(), Instant::now(), |c, acked, now| {
+            let tally = |c: &mut ConnectionState| {
+                let mut tally = Tally::default();
+                let mut ids = HashSet::new();
+                for (index, packet) in acked.iter().enumerate() {
+                    tally.add(
+                        index,
+                        &packet.state,
+                        packet.data_length,
+                        &mut ids,
+                        c.tally_context(),
+                    );
+                }
+                tally
+            };
+            let serial = format!("{:?}", tally(c));
+            let parallel = pool.install(|| Tally::parallel(acked, c.tally_context()));
+            tallies.push((serial, parallel.map(|tally| format!("{tally:?}"))));
+            c.sample_rate(acked, now, Duration::ZERO);
+        });
+        assert!(tallies.iter().filter(|(_, p)| p.is_some()).count() >= 4);
+        assert!(tallies.iter().any(|(_, p)| p.is_none()));
+        for (serial, parallel) in tallies {
+            if let Some(parallel) = parallel {
+                assert_eq!(serial, parallel);
+            }
+        }
+
+        let now = Instant::now();
+        let run = |config| {
+            let mut c = None;
+            let mut samples = vec![];
+            transfer(config, now, |state, acked, now| {
+                samples.push(state.sample_rate(acked, now, Duration::ZERO));
+                c = Some(state.clone());
+            });
+            (c.unwrap(), samples)
+        };
+        let (a, a_samples) = run(serial);
+        let (b, b_samples) = pool.install(|| run(parallel));
+        assert!(a.diff(&b).is_empty(), "{:?}", a.diff(&b));
+        assert_eq!(a_samples, b_samples);
+        assert!(a_samples.iter().flatten().count() >= 4);
+    }
+}
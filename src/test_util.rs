@@ -0,0 +1,270 @@
+//! Helpers for downstream crates to unit-test their logic against the types of this crate
+//!
+//! For testing only: the values are taken as given, with no consistency between the fields enforced.
+
+use std::time::{Duration, Instant};
+
+use crate::{PacketClass, RateSample, Unit};
+
+/// Builds a [`RateSample`] with arbitrary field values
+///
+/// Unset fields are zero, false, or [`None`], except [`RateSample::is_reliable()`] and [`RateSample::confidence()`] which default to true and `1`.
+#[derive(Debug, Clone)]
+pub struct RateSampleBuilder {
+    sample: RateSample,
+}
+impl RateSampleBuilder {
+    pub fn new(prior_time: Instant) -> Self {
+        Self {
+            sample: RateSample {
+                delivery_rate: 0.,
+                is_app_limited: false,
+                interval: Duration::ZERO,
+                delivered: 0,
+                prior_delivered: 0,
+                prior_time,
+                send_elapsed: Duration::ZERO,
+                ack_elapsed: Duration::ZERO,
+                is_quantized: false,
+                is_reliable: true,
+                flight_size_at_ack: None,
+                is_sent_in_recovery: false,
+                is_acked_in_recovery: false,
+                ack_interarrival: None,
+                is_ack_compressed: false,
+                is_post_recovery: false,
+                is_post_migration: false,
+                newest_packet_class: PacketClass::Data,
+                source_delivered: 0,
+                source_delivery_rate: 0.,
+                adjusted_delivered: 0,
+                adjusted_delivery_rate: 0.,
+                context: 0,
+                round: 0,
+                unit: Unit::Octets,
+                max_send_gap: None,
+                newest_packet_id: None,
+                highest_packet_id: None,
+                acked_packet_count: 0,
+                acked_data: 0,
+                ack_frame_count: None,
+                confidence: 1.,
+            },
+        }
+    }
+
+    pub fn build(&self) -> RateSample {
+        self.sample.clone()
+    }
+
+    /// Measured in octets or packets per second
+    pub fn delivery_rate(mut self, delivery_rate: f64) -> Self {
+        self.sample.delivery_rate = delivery_rate;
+        self
+    }
+
+    pub fn is_app_limited(mut self, is_app_limited: bool) -> Self {
+        self.sample.is_app_limited = is_app_limited;
+        self
+    }
+
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.sample.interval = interval;
+        self
+    }
+
+    pub fn delivered(mut self, delivered: u64) -> Self {
+        self.sample.delivered = delivered;
+        self
+    }
+
+    pub fn prior_delivered(mut self, prior_delivered: u64) -> Self {
+        self.sample.prior_delivered = prior_delivered;
+        self
+    }
+
+    pub fn prior_time(mut self, prior_time: Instant) -> Self {
+        self.sample.prior_time = prior_time;
+        self
+    }
+
+    pub fn send_elapsed(mut self, send_elapsed: Duration) -> Self {
+        self.sample.send_elapsed = send_elapsed;
+        self
+    }
+
+    pub fn ack_elapsed(mut self, ack_elapsed: Duration) -> Self {
+        self.sample.ack_elapsed = ack_elapsed;
+        self
+    }
+
+    pub fn is_quantized(mut self, is_quantized: bool) -> Self {
+        self.sample.is_quantized = is_quantized;
+        self
+    }
+
+    pub fn is_reliable(mut self, is_reliable: bool) -> Self {
+        self.sample.is_reliable = is_reliable;
+        self
+    }
+
+    pub fn flight_size_at_ack(mut self, flight_size_at_ack: Option<u64>) -> Self {
+        self.sample.flight_size_at_ack = flight_size_at_ack;
+        self
+    }
+
+    pub fn is_sent_in_recovery(mut self, is_sent_in_recovery: bool) -> Self {
+        self.sample.is_sent_in_recovery = is_sent_in_recovery;
+        self
+    }
+
+    pub fn is_acked_in_recovery(mut self, is_acked_in_recovery: bool) -> Self {
+        self.sample.is_acked_in_recovery = is_acked_in_recovery;
+        self
+    }
+
+    pub fn ack_interarrival(mut self, ack_interarrival: Option<Duration>) -> Self {
+        self.sample.ack_interarrival = ack_interarrival;
+        self
+    }
+
+    pub fn is_ack_compressed(mut self, is_ack_compressed: bool) -> Self {
+        self.sample.is_ack_compressed = is_ack_compressed;
+        self
+    }
+
+    pub fn is_post_recovery(mut self, is_post_recovery: bool) -> Self {
+        self.sample.is_post_recovery = is_post_recovery;
+        self
+    }
+
+    pub fn is_post_migration(mut self, is_post_migration: bool) -> Self {
+        self.sample.is_post_migration = is_post_migration;
+        self
+    }
+
+    pub fn newest_packet_class(mut self, newest_packet_class: PacketClass) -> Self {
+        self.sample.newest_packet_class = newest_packet_class;
+        self
+    }
+
+    pub fn source_delivered(mut self, source_delivered: u64) -> Self {
+        self.sample.source_delivered = source_delivered;
+        self
+    }
+
+    pub fn source_delivery_rate(mut self, source_delivery_rate: f64) -> Self {
+        self.sample.source_delivery_rate = source_delivery_rate;
+        self
+    }
+
+    pub fn adjusted_delivered(mut self, adjusted_delivered: u64) -> Self {
+        self.sample.adjusted_delivered = adjusted_delivered;
+        self
+    }
+
+    pub fn adjusted_delivery_rate(mut self, adjusted_delivery_rate: f64) -> Self {
+        self.sample.adjusted_delivery_rate = adjusted_delivery_rate;
+        self
+    }
+
+    pub fn context(mut self, context: u64) -> Self {
+        self.sample.context = context;
+        self
+    }
+
+    pub fn round(mut self, round: u64) -> Self {
+        self.sample.round = round;
+        self
+    }
+
+    pub fn unit(mut self, unit: Unit) -> Self {
+        self.sample.unit = unit;
+        self
+    }
+
+    pub fn max_send_gap(mut self, max_send_gap: Option<Duration>) -> Self {
+        self.sample.max_send_gap = max_send_gap;
+        self
+    }
+
+    pub fn newest_packet_id(mut self, newest_packet_id: Option<u64>) -> Self {
+        self.sample.newest_packet_id = newest_packet_id;
+        self
+    }
+
+    pub fn highest_packet_id(mut self, highest_packet_id: Option<u64>) -> Self {
+        self.sample.highest_packet_id = highest_packet_id;
+        self
+    }
+
+    pub fn acked_packet_count(mut self, acked_packet_count: u64) -> Self {
+        self.sample.acked_packet_count = acked_packet_count;
+        self
+    }
+
+    /// The data acknowledged by the `ACK`, from which [`RateSample::avg_acked_packet_size()`] is derived
+    pub fn acked_data(mut self, acked_data: u64) -> Self {
+        self.sample.acked_data = acked_data;
+        self
+    }
+
+    pub fn ack_frame_count(mut self, ack_frame_count: Option<u64>) -> Self {
+        self.sample.ack_frame_count = ack_frame_count;
+        self
+    }
+
+    pub fn confidence(mut self, confidence: f32) -> Self {
+        self.sample.confidence = confidence;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_back() {
+        let now = Instant::now();
+        let ms = Duration::from_millis(1);
+        let rs = RateSampleBuilder::new(now)
+            .delivery_rate(1_000.)
+            .is_app_limited(true)
+            .interval(100 * ms)
+            .delivered(100)
+            .prior_delivered(50)
+            .prior_time(now + ms)
+            .send_elapsed(90 * ms)
+            .ack_elapsed(100 * ms)
+            .is_quantized(true)
+            .is_reliable(false)
+            .flight_size_at_ack(Some(10))
+            .is_sent_in_recovery(true)
+            .is_acked_in_recovery(true)
+            .ack_interarrival(Some(ms))
+            .is_ack_compressed(true)
+            .is_post_recovery(true)
+            .build();
+        assert_eq!(rs.delivery_rate_raw(), 1_000.);
+        assert!(rs.is_app_limited());
+        assert_eq!(rs.interval(), 100 * ms);
+        assert_eq!(rs.delivered(), 100);
+        assert_eq!(rs.prior_delivered(), 50);
+        assert_eq!(rs.prior_time(), now + ms);
+        assert_eq!(rs.send_elapsed(), 90 * ms);
+        assert_eq!(rs.ack_elapsed(), 100 * ms);
+        assert!(rs.is_quantized());
+        assert!(!rs.is_reliable());
+        assert_eq!(rs.flight_size_at_ack(), Some(10));
+        assert!(rs.is_sent_in_recovery());
+        assert!(rs.is_acked_in_recovery());
+        assert_eq!(rs.ack_interarrival(), Some(ms));
+        assert!(rs.is_ack_compressed());
+        assert!(rs.is_post_recovery());
+
+        let rs = RateSampleBuilder::new(now).build();
+        assert_eq!(rs.delivery_rate_raw(), 0.);
+        assert!(rs.is_reliable());
+    }
+}
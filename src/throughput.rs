@@ -0,0 +1,101 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::DeliveryRate;
+
+/// A time-averaged delivery rate over a sliding window, e.g. for a live throughput gauge
+///
+/// Unlike a windowed-max bandwidth filter, this reports the data delivered within the window divided by the window length, so it is independent of the `ACK` cadence.
+///
+/// Memory: one entry per `ACK` within `max_window`; older entries are evicted on each [`Self::on_ack()`].
+#[derive(Debug, Clone)]
+pub struct RecentThroughput {
+    max_window: Duration,
+    /// (ACK time, delivered delta), oldest first
+    acks: VecDeque<(Instant, u64)>,
+}
+impl RecentThroughput {
+    /// `max_window`: the longest window [`Self::rate_over()`] is asked for
+    pub fn new(max_window: Duration) -> Self {
+        Self {
+            max_window,
+            acks: VecDeque::new(),
+        }
+    }
+
+    /// `delivered`: the data (measured in octets or packets) newly delivered by this `ACK`
+    pub fn on_ack(&mut self, now: Instant, delivered: u64) {
+        self.evict(now);
+        self.acks.push_back((now, delivered));
+    }
+
+    /// The data delivered within `(now - window, now]` divided by `window`
+    ///
+    /// `window` is capped at `max_window`; [`None`] if `window` is zero.
+    pub fn rate_over(&self, window: Duration, now: Instant) -> Option<DeliveryRate> {
+        let window = window.min(self.max_window);
+        if window.is_zero() {
+            return None;
+        }
+        let delivered: u64 = self
+            .acks
+            .iter()
+            .rev()
+            .skip_while(|(t, _)| now < *t)
+            .take_while(|(t, _)| now.saturating_duration_since(*t) < window)
+            .map(|(_, delivered)| delivered)
+            .sum();
+        Some(DeliveryRate::from_bytes_per_sec(
+            delivered as f64 / window.as_secs_f64(),
+        ))
+    }
+
+    fn evict(&mut self, now: Instant) {
+        while self
+            .acks
+            .front()
+            .is_some_and(|(t, _)| self.max_window <= now.saturating_duration_since(*t))
+        {
+            self.acks.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bursty_acks_smooth() {
+        let start = Instant::now();
+        let ms = Duration::from_millis(1);
+        let window = 250 * ms;
+        let mut throughput = RecentThroughput::new(window);
+        // 10_000 octets acknowledged at once every 50 ms: 200_000 octets per second
+        let mut rates = vec![];
+        for k in 1..=40 {
+            let now = start + k * 50 * ms;
+            throughput.on_ack(now, 10_000);
+            for poll in [0, 25] {
+                let rate = throughput.rate_over(window, now + poll * ms).unwrap();
+                rates.push(rate.as_bytes_per_sec());
+            }
+        }
+        for rate in &rates[10..] {
+            assert_eq!(*rate, 200_000.);
+        }
+        assert!(throughput.acks.len() <= 5);
+    }
+
+    #[test]
+    fn test_window_capped() {
+        let now = Instant::now();
+        let mut throughput = RecentThroughput::new(Duration::from_secs(1));
+        throughput.on_ack(now, 1_000);
+        let rate = throughput.rate_over(Duration::from_secs(10), now).unwrap();
+        assert_eq!(rate.as_bytes_per_sec(), 1_000.);
+        assert_eq!(throughput.rate_over(Duration::ZERO, now), None);
+    }
+}
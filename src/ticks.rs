@@ -0,0 +1,121 @@
+use std::{
+    ops::Add,
+    time::{Duration, Instant},
+};
+
+/// A timestamp read from a free-running 32-bit millisecond tick counter, which wraps every ~49.7 days
+///
+/// Differences are taken modulo 2^32, so they are correct across the wrap as long as the measured interval is shorter than [`Self::SAFE_HORIZON`] (half the wrap period).
+/// A longer interval is indistinguishable from a shorter one, or from a negative one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WrappingTicks32(u32);
+impl WrappingTicks32 {
+    /// The longest interval [`Self::duration_since()`] measures correctly, ~24.8 days
+    pub const SAFE_HORIZON: Duration = Duration::from_millis(1 << 31);
+
+    pub fn from_millis(ticks: u32) -> Self {
+        Self(ticks)
+    }
+
+    pub fn as_millis(&self) -> u32 {
+        self.0
+    }
+
+    /// Wraparound-correct elapsed time from `earlier`
+    ///
+    /// `earlier` must be at most [`Self::SAFE_HORIZON`] before `self`; checked by a debug assertion.
+    pub fn duration_since(&self, earlier: Self) -> Duration {
+        let ticks = self.0.wrapping_sub(earlier.0);
+        debug_assert!(
+            ticks < 1 << 31,
+            "interval of {ticks} ms exceeds the safe horizon; {earlier:?} is later than {self:?} or too far behind it"
+        );
+        Duration::from_millis(u64::from(ticks))
+    }
+}
+impl Add<Duration> for WrappingTicks32 {
+    type Output = Self;
+
+    /// Wraps; sub-millisecond parts are truncated
+    fn add(self, rhs: Duration) -> Self::Output {
+        Self(self.0.wrapping_add(rhs.as_millis() as u32))
+    }
+}
+
+/// Map [`WrappingTicks32`] onto a monotonic [`Instant`] timeline for the sampling pipeline
+///
+/// Ticks must be fed in non-decreasing order, each within [`WrappingTicks32::SAFE_HORIZON`] of the previous one.
+#[derive(Debug, Clone)]
+pub struct TickUnwrapper {
+    base: Instant,
+    last: WrappingTicks32,
+    elapsed: Duration,
+}
+impl TickUnwrapper {
+    /// `base` is the [`Instant`] that `start` maps to
+    pub fn new(start: WrappingTicks32, base: Instant) -> Self {
+        Self {
+            base,
+            last: start,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    pub fn to_instant(&mut self, t: WrappingTicks32) -> Instant {
+        self.elapsed += t.duration_since(self.last);
+        self.last = t;
+        self.base + self.elapsed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ConnectionState, Packet};
+
+    #[test]
+    fn test_duration_since_across_wrap() {
+        let before = WrappingTicks32::from_millis(u32::MAX - 9);
+        let after = before + Duration::from_millis(30);
+        assert_eq!(after.as_millis(), 20);
+        assert_eq!(after.duration_since(before), Duration::from_millis(30));
+    }
+
+    #[test]
+    #[should_panic]
+    #[cfg(debug_assertions)]
+    fn test_beyond_horizon() {
+        let earlier = WrappingTicks32::from_millis(100);
+        earlier.duration_since(WrappingTicks32::from_millis(200));
+    }
+
+    #[test]
+    fn test_sampling_across_wrap() {
+        let ms = Duration::from_millis(1);
+        let start = WrappingTicks32::from_millis(u32::MAX - 150);
+        let mut clock = TickUnwrapper::new(start, Instant::now());
+        let mut c = ConnectionState::new(clock.to_instant(start));
+        let mut rates = vec![];
+        // 1_000 octets every 100 ms, acked 100 ms later; the counter wraps mid-way
+        let mut t = start;
+        let mut in_flight = None;
+        for _ in 0..4 {
+            let now = clock.to_instant(t);
+            if let Some(state) = in_flight.take() {
+                let packet = Packet {
+                    state,
+                    data_length: 1_000,
+                };
+                rates.extend(c.sample_rate(&[packet], now, Duration::ZERO));
+            }
+            in_flight = Some(c.send_packet_2(now, true));
+            t = t + 100 * ms;
+        }
+        assert!(t.as_millis() < 1_000);
+        let rates: Vec<f64> = rates
+            .iter()
+            .map(|rs| rs.delivery_rate().as_bytes_per_sec())
+            .collect();
+        assert_eq!(rates, [10_000., 10_000.]);
+    }
+}
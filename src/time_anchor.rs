@@ -0,0 +1,172 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// A paired [`Instant`] and [`SystemTime`] for rendering `Instant`s as real-world timestamps in exports
+///
+/// Create one per exporter (or process) and share it, so every export agrees on the mapping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeAnchor {
+    instant: Instant,
+    system: SystemTime,
+}
+impl TimeAnchor {
+    pub fn now() -> Self {
+        Self::new(Instant::now(), SystemTime::now())
+    }
+
+    /// `instant` and `system` should be captured at the same moment
+    pub fn new(instant: Instant, system: SystemTime) -> Self {
+        Self { instant, system }
+    }
+
+    /// `t` may be earlier than the anchor
+    ///
+    /// [`None`] if the result is out of the range of [`SystemTime`] on this platform.
+    pub fn to_system(&self, t: Instant) -> Option<SystemTime> {
+        match t.checked_duration_since(self.instant) {
+            Some(after) => self.system.checked_add(after),
+            None => self.system.checked_sub(self.instant - t),
+        }
+    }
+
+    /// Microseconds since the Unix epoch; negative before it
+    ///
+    /// [`None`] if [`Self::to_system()`] is; saturates at the range of `i64`.
+    pub fn to_unix_micros(&self, t: Instant) -> Option<i64> {
+        let micros = match self.to_system(t)?.duration_since(UNIX_EPOCH) {
+            Ok(after) => SignedDuration::Ahead(after),
+            Err(e) => SignedDuration::Behind(e.duration()),
+        };
+        Some(micros.as_micros())
+    }
+
+    /// The inverse of [`Self::to_system()`]
+    ///
+    /// [`None`] if `system` is out of the range of [`Instant`] on this platform.
+    pub fn to_instant(&self, system: SystemTime) -> Option<Instant> {
+        match system.duration_since(self.system) {
+            Ok(after) => self.instant.checked_add(after),
+            Err(e) => self.instant.checked_sub(e.duration()),
+        }
+    }
+
+    /// Re-anchor to the current time, returning how far the wall clock drifted from the old mapping
+    ///
+    /// The wall clock can be stepped or slewed (e.g. by NTP) while [`Instant`] is monotonic, so a long-lived anchor slowly goes out of date.
+    /// The drift is positive if the wall clock is now ahead of what the old anchor predicted.
+    pub fn recalibrate(&mut self) -> SignedDuration {
+        self.recalibrate_to(Instant::now(), SystemTime::now())
+    }
+
+    fn recalibrate_to(&mut self, instant: Instant, system: SystemTime) -> SignedDuration {
+        // The wall clock elapsed minus the monotonic clock elapsed, without computing a possibly unrepresentable prediction
+        let wall = match system.duration_since(self.system) {
+            Ok(after) => SignedDuration::Ahead(after),
+            Err(e) => SignedDuration::Behind(e.duration()),
+        };
+        let monotonic = match instant.checked_duration_since(self.instant) {
+            Some(after) => SignedDuration::Ahead(after),
+            None => SignedDuration::Behind(self.instant - instant),
+        };
+        *self = Self::new(instant, system);
+        SignedDuration::from_nanos(wall.as_nanos() - monotonic.as_nanos())
+    }
+}
+
+/// A [`Duration`] with a direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignedDuration {
+    Ahead(Duration),
+    Behind(Duration),
+}
+impl SignedDuration {
+    /// Saturates at the range of `i64`
+    pub fn as_micros(&self) -> i64 {
+        let micros = self.as_nanos() / 1_000;
+        i64::try_from(micros).unwrap_or(if micros < 0 { i64::MIN } else { i64::MAX })
+    }
+
+    fn as_nanos(&self) -> i128 {
+        match self {
+            Self::Ahead(d) => d.as_nanos() as i128,
+            Self::Behind(d) => -(d.as_nanos() as i128),
+        }
+    }
+
+    /// Saturates at [`Duration::MAX`]
+    fn from_nanos(nanos: i128) -> Self {
+        const NANOS_PER_SEC: u128 = 1_000_000_000;
+        let abs = nanos.unsigned_abs();
+        let duration = u64::try_from(abs / NANOS_PER_SEC).map_or(Duration::MAX, |secs| {
+            Duration::new(secs, (abs % NANOS_PER_SEC) as u32)
+        });
+        if nanos < 0 {
+            Self::Behind(duration)
+        } else {
+            Self::Ahead(duration)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_both_directions() {
+        let t0 = Instant::now();
+        let s = Duration::from_secs(1);
+        let anchor = TimeAnchor::new(t0 + 10 * s, UNIX_EPOCH + 1_000 * s);
+
+        assert_eq!(anchor.to_system(t0 + 12 * s), Some(UNIX_EPOCH + 1_002 * s));
+        assert_eq!(anchor.to_unix_micros(t0 + 12 * s), Some(1_002_000_000));
+        assert_eq!(anchor.to_instant(UNIX_EPOCH + 1_002 * s), Some(t0 + 12 * s));
+
+        // Earlier than the anchor
+        assert_eq!(anchor.to_system(t0), Some(UNIX_EPOCH + 990 * s));
+        assert_eq!(anchor.to_unix_micros(t0), Some(990_000_000));
+        assert_eq!(anchor.to_instant(UNIX_EPOCH + 990 * s), Some(t0));
+    }
+
+    #[test]
+    fn test_before_unix_epoch() {
+        let t0 = Instant::now();
+        let s = Duration::from_secs(1);
+        let anchor = TimeAnchor::new(t0 + 10 * s, UNIX_EPOCH + 5 * s);
+        assert_eq!(anchor.to_unix_micros(t0 + s), Some(-4_000_000));
+        assert_eq!(anchor.to_unix_micros(t0 + 10 * s / 4), Some(-2_500_000));
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        let t0 = Instant::now();
+        let anchor = TimeAnchor::new(t0 + Duration::from_secs(10), UNIX_EPOCH);
+        // Platforms differ in how far before the Unix epoch `SystemTime` reaches
+        if let Some(micros) = anchor.to_unix_micros(t0) {
+            assert_eq!(micros, -10_000_000);
+        }
+        let anchor = TimeAnchor::new(
+            t0,
+            UNIX_EPOCH + Duration::from_secs(9_000_000_000_000_000_000),
+        );
+        let far = Duration::from_secs(1_000_000_000_000_000_000);
+        assert_eq!(anchor.to_system(t0 + far), None);
+        assert_eq!(anchor.to_unix_micros(t0 + far), None);
+        assert_eq!(anchor.to_unix_micros(t0), Some(i64::MAX));
+        assert_eq!(SignedDuration::Behind(Duration::MAX).as_micros(), i64::MIN);
+    }
+
+    #[test]
+    fn test_recalibrate() {
+        let t0 = Instant::now();
+        let s = Duration::from_secs(1);
+        let mut anchor = TimeAnchor::new(t0, UNIX_EPOCH + 1_000 * s);
+        // The wall clock was stepped back by 2 s
+        let drift = anchor.recalibrate_to(t0 + 10 * s, UNIX_EPOCH + 1_008 * s);
+        assert_eq!(drift, SignedDuration::Behind(2 * s));
+        assert_eq!(drift.as_micros(), -2_000_000);
+        assert_eq!(anchor.to_system(t0 + 11 * s), Some(UNIX_EPOCH + 1_009 * s));
+
+        let mut anchor = TimeAnchor::now();
+        assert!(anchor.recalibrate().as_micros().abs() < 1_000_000);
+    }
+}
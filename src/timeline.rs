@@ -0,0 +1,96 @@
+use std::time::Instant;
+
+use crate::RateSample;
+
+/// Recorded rate samples turned into a queryable function of time
+///
+/// Each sample covers the span from [`RateSample::prior_time()`] to [`RateSample::prior_time()`] + [`RateSample::interval()`].
+#[derive(Debug, Clone, Default)]
+pub struct RateTimeline {
+    /// Sorted by [`Span::start`]
+    spans: Vec<Span>,
+}
+impl RateTimeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, sample: &RateSample) {
+        let span = Span {
+            start: sample.prior_time(),
+            end: sample.prior_time() + sample.interval(),
+            rate: sample.delivery_rate_raw(),
+        };
+        // Keep the insertion order among spans of the same start
+        let i = self.spans.partition_point(|s| s.start <= span.start);
+        self.spans.insert(i, span);
+    }
+
+    /// The delivery rate as seen at `t`
+    ///
+    /// - Inside the span of a sample: the rate of the most recently started span covering `t`.
+    /// - Between spans: linearly interpolated from the end of the previous span to the start of the next span.
+    /// - Before the first span or after the last span: [`None`].
+    pub fn rate_at(&self, t: Instant) -> Option<f64> {
+        // Spans started at or before `t`
+        let started = &self.spans[..self.spans.partition_point(|s| s.start <= t)];
+
+        if let Some(covering) = started.iter().rev().find(|s| t <= s.end) {
+            return Some(covering.rate);
+        }
+
+        let previous = started.iter().max_by_key(|s| s.end)?;
+        let next = self.spans.get(started.len())?;
+        let gap = (next.start - previous.end).as_secs_f64();
+        let progress = (t - previous.end).as_secs_f64() / gap;
+        Some(previous.rate + (next.rate - previous.rate) * progress)
+    }
+
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Span {
+    start: Instant,
+    end: Instant,
+    rate: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn sample(prior_time: Instant, interval: Duration, delivery_rate: f64) -> RateSample {
+        RateSample::synthetic(delivery_rate, false, prior_time, interval)
+    }
+
+    #[test]
+    fn test_rate_at() {
+        let t0 = Instant::now();
+        let s = Duration::from_secs(1);
+        let mut tl = RateTimeline::new();
+        tl.push(&sample(t0 + 4 * s, 2 * s, 30.));
+        tl.push(&sample(t0, 2 * s, 10.));
+        tl.push(&sample(t0 + s, 2 * s, 20.));
+
+        assert_eq!(tl.rate_at(t0), Some(10.));
+        // Overlapping spans pick the most recently started one
+        assert_eq!(tl.rate_at(t0 + s / 2), Some(10.));
+        assert_eq!(tl.rate_at(t0 + s + s / 2), Some(20.));
+        assert_eq!(tl.rate_at(t0 + 3 * s), Some(20.));
+        // Between 3s and 4s: interpolated from 20 to 30
+        assert_eq!(tl.rate_at(t0 + 3 * s + s / 2), Some(25.));
+        assert_eq!(tl.rate_at(t0 + 6 * s), Some(30.));
+
+        assert_eq!(tl.rate_at(t0 + 7 * s), None);
+        assert_eq!(RateTimeline::new().rate_at(t0), None);
+    }
+}
@@ -0,0 +1,279 @@
+//! Rate sampling on a worker thread, off the datapath
+//!
+//! The datapath submits [`SamplerEvent`]s through a [`SamplerHandle`]; the worker owns the [`ConnectionState`], the packet states, and a bandwidth estimator, and publishes the estimate to a [`BandwidthCell`].
+//!
+//! # Ordering
+//!
+//! Events are processed in submission order: one worker serves one connection through one FIFO queue.
+//! Events submitted concurrently through clones of the handle are ordered by whichever enqueues first.
+//!
+//! # Shutdown
+//!
+//! Once every [`SamplerHandle`] is dropped, the worker drains the events still queued, publishes the final estimate, and exits; [`SamplerWorker::join()`] then returns the [`ConnectionState`].
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{sync_channel, Receiver, SyncSender, TrySendError},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crate::{estimator::BandwidthEstimator, ConnectionState, DeliveryRate, PacketState};
+
+/// What the datapath reports to the worker
+#[derive(Debug, Clone)]
+pub enum SamplerEvent {
+    /// A packet was transmitted
+    Send {
+        pkt_num: u64,
+        now: Instant,
+        no_packets_in_flight: bool,
+        /// Measured in octets or packets
+        data_length: u64,
+    },
+    /// An `ACK` acknowledged these packets; unknown packet numbers are skipped
+    Ack {
+        pkt_nums: Vec<u64>,
+        now: Instant,
+        min_rtt: Duration,
+    },
+    /// These packets are declared lost and forgotten
+    Lost { pkt_nums: Vec<u64> },
+}
+
+/// The datapath's handle to a [`SamplerWorker`]; cheap to clone
+#[derive(Debug, Clone)]
+pub struct SamplerHandle {
+    tx: SyncSender<SamplerEvent>,
+    dropped: Arc<AtomicU64>,
+}
+impl SamplerHandle {
+    /// Never blocks: an event submitted while the queue is full (or after the worker exited) is dropped and counted
+    ///
+    /// Return whether the event was queued.
+    /// A dropped [`SamplerEvent::Send`] leaves its packet unknown to the worker, so its `ACK` is skipped.
+    pub fn send_event(&self, event: SamplerEvent) -> bool {
+        match self.tx.try_send(event) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+        }
+    }
+
+    /// The number of events dropped so far
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// The latest bandwidth estimate published by a [`SamplerWorker`], readable from any thread
+#[derive(Debug, Clone)]
+pub struct BandwidthCell(Arc<AtomicU64>);
+impl BandwidthCell {
+    /// Not a valid `f64` bit pattern produced by the estimators
+    const NONE: u64 = u64::MAX;
+
+    fn new() -> Self {
+        Self(Arc::new(AtomicU64::new(Self::NONE)))
+    }
+
+    fn set(&self, bandwidth: Option<DeliveryRate>) {
+        let bits = bandwidth.map_or(Self::NONE, |bw| bw.as_bytes_per_sec().to_bits());
+        self.0.store(bits, Ordering::Release);
+    }
+
+    /// [`None`] until the estimator produced an estimate
+    pub fn get(&self) -> Option<DeliveryRate> {
+        let bits = self.0.load(Ordering::Acquire);
+        (bits != Self::NONE).then(|| DeliveryRate::from_bytes_per_sec(f64::from_bits(bits)))
+    }
+}
+
+/// A thread owning a [`ConnectionState`] and a bandwidth estimator
+#[derive(Debug)]
+pub struct SamplerWorker {
+    thread: JoinHandle<ConnectionState>,
+}
+impl SamplerWorker {
+    /// `capacity`: the number of events the queue holds before [`SamplerHandle::send_event()`] drops
+    pub fn spawn(
+        state: ConnectionState,
+        estimator: Box<dyn BandwidthEstimator + Send>,
+        capacity: usize,
+    ) -> (Self, SamplerHandle, BandwidthCell) {
+        let (handle, rx) = queue(capacity);
+        let cell = BandwidthCell::new();
+        let worker = Self::start(rx, state, estimator, cell.clone());
+        (worker, handle, cell)
+    }
+
+    fn start(
+        rx: Receiver<SamplerEvent>,
+        mut state: ConnectionState,
+        mut estimator: Box<dyn BandwidthEstimator + Send>,
+        cell: BandwidthCell,
+    ) -> Self {
+        let thread = std::thread::spawn(move || {
+            let mut in_flight: HashMap<u64, (PacketState, u64)> = HashMap::new();
+            for event in rx {
+                match event {
+                    SamplerEvent::Send {
+                        pkt_num,
+                        now,
+                        no_packets_in_flight,
+                        data_length,
+                    } => {
+                        let packet = state.send_packet_2(now, no_packets_in_flight);
+                        in_flight.insert(pkt_num, (packet, data_length));
+                    }
+                    SamplerEvent::Ack {
+                        pkt_nums,
+                        now,
+                        min_rtt,
+                    } => {
+                        let acked = pkt_nums.iter().filter_map(|n| in_flight.remove(n));
+                        if let Some(rs) = state.sample_rate_iter(acked, now, min_rtt) {
+                            estimator.on_sample(&rs);
+                            cell.set(estimator.bandwidth());
+                        }
+                    }
+                    SamplerEvent::Lost { pkt_nums } => {
+                        for pkt_num in pkt_nums {
+                            in_flight.remove(&pkt_num);
+                        }
+                    }
+                }
+            }
+            state
+        });
+        Self { thread }
+    }
+
+    /// Wait for the worker to drain and exit once every [`SamplerHandle`] is dropped
+    pub fn join(self) -> ConnectionState {
+        self.thread.join().expect("sampler worker panicked")
+    }
+}
+
+fn queue(capacity: usize) -> (SamplerHandle, Receiver<SamplerEvent>) {
+    let (tx, rx) = sync_channel(capacity);
+    let handle = SamplerHandle {
+        tx,
+        dropped: Arc::new(AtomicU64::new(0)),
+    };
+    (handle, rx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::estimator::WindowedMaxBandwidth;
+
+    /// 1_000 octets every 10 ms over a 50 ms RTT
+    fn events(start: Instant) -> Vec<SamplerEvent> {
+        let ms = Duration::from_millis(1);
+        let mut events = vec![];
+        for k in 0..100 {
+            let now = start + k * 10 * ms;
+            if 5 <= k {
+                events.push(SamplerEvent::Ack {
+                    pkt_nums: vec![k as u64 - 5],
+                    now,
+                    min_rtt: Duration::ZERO,
+                });
+            }
+            events.push(SamplerEvent::Send {
+                pkt_num: k as u64,
+                now,
+                no_packets_in_flight: k == 0,
+                data_length: 1_000,
+            });
+        }
+        events
+    }
+
+    #[test]
+    fn test_matches_inline() {
+        let start = Instant::now();
+        let events = events(start);
+
+        // Inline control
+        let mut c = ConnectionState::new(start);
+        let mut estimator = WindowedMaxBandwidth::new(10);
+        let mut in_flight = HashMap::new();
+        for event in events.clone() {
+            match event {
+                SamplerEvent::Send {
+                    pkt_num,
+                    now,
+                    no_packets_in_flight,
+                    data_length,
+                } => {
+                    in_flight.insert(
+                        pkt_num,
+                        (c.send_packet_2(now, no_packets_in_flight), data_length),
+                    );
+                }
+                SamplerEvent::Ack {
+                    pkt_nums,
+                    now,
+                    min_rtt,
+                } => {
+                    let acked = pkt_nums.iter().filter_map(|n| in_flight.remove(n));
+                    if let Some(rs) = c.sample_rate_iter(acked, now, min_rtt) {
+                        estimator.on_sample(&rs);
+                    }
+                }
+                SamplerEvent::Lost { .. } => unreachable!(),
+            }
+        }
+
+        let (worker, handle, cell) = SamplerWorker::spawn(
+            ConnectionState::new(start),
+            Box::new(WindowedMaxBandwidth::new(10)),
+            events.len(),
+        );
+        let producer = std::thread::spawn(move || {
+            for event in events {
+                assert!(handle.send_event(event));
+            }
+        });
+        producer.join().unwrap();
+        let state = worker.join();
+        assert_eq!(cell.get(), estimator.bandwidth());
+        assert_eq!(cell.get().unwrap().as_bytes_per_sec(), 100_000.);
+        assert!(state.diff(&c).is_empty());
+    }
+
+    #[test]
+    fn test_drop_accounting() {
+        let start = Instant::now();
+        let (handle, rx) = queue(2);
+        let events = events(start);
+        let accepted = events
+            .iter()
+            .take(3)
+            .filter(|event| handle.send_event((*event).clone()))
+            .count();
+        assert_eq!(accepted, 2);
+        assert_eq!(handle.dropped(), 1);
+
+        // The queued events are drained on shutdown
+        let cell = BandwidthCell::new();
+        let worker = SamplerWorker::start(
+            rx,
+            ConnectionState::new(start),
+            Box::new(WindowedMaxBandwidth::new(10)),
+            cell.clone(),
+        );
+        drop(handle);
+        worker.join();
+        assert_eq!(cell.get(), None);
+    }
+}
@@ -0,0 +1,130 @@
+//! [`S2nQuicAdapter`] driven through s2n-quic's `CongestionController` callbacks with s2n-quic's testing utilities
+#![cfg(feature = "s2n-quic")]
+
+use std::{collections::VecDeque, time::Duration};
+
+use dre::s2n_quic::{S2nQuicAdapter, S2nQuicEndpoint};
+use s2n_quic_core::{
+    event, inet,
+    packet::number::PacketNumberSpace,
+    path, random,
+    recovery::{
+        congestion_controller::{Endpoint, PathInfo, PathPublisher},
+        CongestionController, RttEstimator,
+    },
+    time::clock::testing as clock,
+};
+
+fn new_adapter() -> (S2nQuicAdapter, u16) {
+    let mtu = path::mtu::Config::default();
+    let remote = inet::SocketAddress::default();
+    let info = PathInfo::new(&mtu, &remote);
+    let max_datagram_size = info.max_datagram_size;
+    let adapter = S2nQuicEndpoint::default().new_congestion_controller(info);
+    (adapter, max_datagram_size)
+}
+
+#[test]
+fn test_transfer() {
+    // 1_000_000 octets per second behind a 50 ms RTT
+    let ms = Duration::from_millis(1);
+    let mut publisher = event::testing::Publisher::no_snapshot();
+    let mut publisher = PathPublisher::new(&mut publisher, path::Id::test_id());
+    let random = &mut random::testing::Generator::default();
+    let mut rtt = RttEstimator::new(100 * ms);
+    let (mut a, mss) = new_adapter();
+    let serialization = Duration::from_secs_f64(f64::from(mss) / 1_000_000.);
+
+    clock::reset();
+    let start = clock::now();
+    let mut link_free = start;
+    // (ack time, send time, packet info)
+    let mut acks = VecDeque::new();
+    let mut to_send = 2_000_u32;
+    while 0 < to_send || !acks.is_empty() {
+        let now = clock::now();
+        while let Some((_, sent, info)) = acks.pop_front_if(|(t, _, _)| *t <= now) {
+            rtt.update_rtt(
+                Duration::ZERO,
+                now - sent,
+                now,
+                true,
+                PacketNumberSpace::ApplicationData,
+            );
+            a.on_rtt_update(sent, now, &rtt, &mut publisher);
+            a.on_ack(
+                sent,
+                usize::from(mss),
+                info,
+                &rtt,
+                random,
+                now,
+                &mut publisher,
+            );
+        }
+        while 0 < to_send && !a.is_congestion_limited() {
+            let info = a.on_packet_sent(now, usize::from(mss), Some(false), &rtt, &mut publisher);
+            link_free = link_free.max(now) + serialization;
+            acks.push_back((link_free + 50 * ms, now, info));
+            to_send -= 1;
+        }
+        clock::advance(ms);
+    }
+    assert_eq!(a.bytes_in_flight(), 0);
+    let bw = a.bandwidth().unwrap().as_bytes_per_sec();
+    assert!((bw - 1_000_000.).abs() < 50_000., "{bw}");
+    // Two BDPs
+    let cwnd = a.congestion_window();
+    assert!((f64::from(cwnd) - 100_000.).abs() < 5_000., "{cwnd}");
+    // The transfer took little more than its serialization time
+    let elapsed = clock::now() - start;
+    let serialization = serialization * 2_000;
+    assert!(elapsed < serialization + 200 * ms, "{elapsed:?}");
+}
+
+#[test]
+fn test_loss_and_congestion_events() {
+    let mut publisher = event::testing::Publisher::no_snapshot();
+    let mut publisher = PathPublisher::new(&mut publisher, path::Id::test_id());
+    let random = &mut random::testing::Generator::default();
+    let rtt = RttEstimator::new(Duration::from_millis(100));
+    let (mut a, mss) = new_adapter();
+    assert_eq!(a.congestion_window(), 10 * u32::from(mss));
+
+    clock::reset();
+    let now = clock::now();
+    let bytes = usize::from(mss);
+    let info = a.on_packet_sent(now, bytes, None, &rtt, &mut publisher);
+    a.on_packet_sent(now, bytes, None, &rtt, &mut publisher);
+    a.on_packet_lost(
+        u32::from(mss),
+        info,
+        false,
+        true,
+        random,
+        now,
+        &mut publisher,
+    );
+    a.on_packet_discarded(bytes, &mut publisher);
+    assert_eq!(a.bytes_in_flight(), 0);
+    assert_eq!(a.connection_state().stats().lost, u64::from(mss));
+    assert_eq!(a.connection_state().current_epoch(), 1);
+    a.on_explicit_congestion(0, now, &mut publisher);
+    a.on_explicit_congestion(2, now, &mut publisher);
+    assert_eq!(a.connection_state().current_epoch(), 2);
+}
+
+#[test]
+fn test_saturating_byte_counts() {
+    let mut publisher = event::testing::Publisher::no_snapshot();
+    let mut publisher = PathPublisher::new(&mut publisher, path::Id::test_id());
+    let rtt = RttEstimator::new(Duration::from_millis(100));
+    let (mut a, _) = new_adapter();
+
+    clock::reset();
+    let huge = u32::MAX as usize + 10;
+    a.on_packet_sent(clock::now(), huge, None, &rtt, &mut publisher);
+    assert_eq!(a.bytes_in_flight(), u32::MAX);
+    a.on_packet_discarded(huge, &mut publisher);
+    assert_eq!(a.bytes_in_flight(), 0);
+}